@@ -19,6 +19,13 @@ pub struct ServerConfig {
     pub port: u16,
     pub db_url: String,
     pub base_dir: PathBuf,
+    //how long after sending the author can still edit a message
+    #[serde(default = "default_message_edit_window_secs")]
+    pub message_edit_window_secs: u64,
+}
+
+fn default_message_edit_window_secs() -> u64 {
+    15 * 60
 }
 
 impl AppConfig {
@@ -43,6 +43,36 @@ pub enum AppError {
     MessageCreateError(String),
     #[error("Chat file error {0}")]
     ChatFileError(String),
+    #[error("chat is archived: {0}")]
+    ChatArchived(String),
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("update chat error {0}")]
+    UpdateChatError(String),
+    #[error("sidebar section error {0}")]
+    SidebarSectionError(String),
+    #[error("shared channel error {0}")]
+    SharedChannelError(String),
+    #[error("invite error {0}")]
+    InviteError(String),
+    #[error("invite is no longer valid: {0}")]
+    InviteExpired(String),
+    #[error("posting not allowed: {0}")]
+    PostNotAllowed(String),
+    #[error("slow mode is on, try again in {0} seconds")]
+    SlowMode(u64),
+    #[error("block error {0}")]
+    BlockError(String),
+    #[error("user group error {0}")]
+    UserGroupError(String),
+    #[error("reaction error {0}")]
+    ReactionError(String),
+    #[error("pin error {0}")]
+    PinError(String),
+    #[error("search error {0}")]
+    SearchError(String),
+    #[error("list messages error {0}")]
+    ListMessagesError(String),
 }
 
 impl ErrorOutput {
@@ -57,6 +87,21 @@ impl IntoResponse for AppError {
     fn into_response(self) -> Response<Body> {
         let status_code = match &self {
             AppError::ChatFileError(_) => axum::http::StatusCode::NOT_FOUND,
+            AppError::ChatArchived(_) => axum::http::StatusCode::FORBIDDEN,
+            AppError::PermissionDenied(_) => axum::http::StatusCode::FORBIDDEN,
+            AppError::UpdateChatError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::SidebarSectionError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::SharedChannelError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::InviteError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::InviteExpired(_) => axum::http::StatusCode::GONE,
+            AppError::PostNotAllowed(_) => axum::http::StatusCode::FORBIDDEN,
+            AppError::SlowMode(_) => axum::http::StatusCode::TOO_MANY_REQUESTS,
+            AppError::BlockError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::UserGroupError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::ReactionError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::PinError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::SearchError(_) => axum::http::StatusCode::BAD_REQUEST,
+            AppError::ListMessagesError(_) => axum::http::StatusCode::BAD_REQUEST,
             AppError::MessageCreateError(_) => axum::http::StatusCode::BAD_REQUEST,
             AppError::InternalError(_) => axum::http::StatusCode::BAD_REQUEST,
             AppError::UploadFileError(_) => axum::http::StatusCode::BAD_REQUEST,
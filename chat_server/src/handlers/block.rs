@@ -0,0 +1,96 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{BlockReport, BlockUser, BlockedUser},
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::User;
+
+#[utoipa::path(
+    get,
+    path = "/api/blocks",
+    responses(
+        (status = 200, description = "Users blocked by current user", body=Vec<BlockedUser>)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_blocked_users_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let users = state.list_blocked_users(user.id as _).await?;
+    Ok((StatusCode::OK, Json(users)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/blocks",
+    request_body = BlockUser,
+    responses(
+        (status = 201, description = "User blocked", body=BlockedUser),
+        (status = 400, description = "Invalid block", body=ErrorOutput),
+        (status = 404, description = "User not in the workspace", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn block_user_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Json(input): Json<BlockUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let blocked = state.block_user(input, &user).await?;
+    Ok((StatusCode::CREATED, Json(blocked)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/blocks/{id}",
+    params(("id"=u64, Path, description="Blocked user ID")),
+    responses(
+        (status = 204, description = "User unblocked"),
+        (status = 404, description = "User not blocked", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn unblock_user_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.unblock_user(id, user.id as _).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/blocks/reports",
+    responses(
+        (status = 200, description = "Blocked users of the workspace, most blocked first", body=Vec<BlockReport>),
+        (status = 403, description = "Not a workspace admin", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_block_reports_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let reports = state.list_block_reports(&user).await?;
+    Ok((StatusCode::OK, Json(reports)))
+}
@@ -1,23 +1,28 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     Extension, Json,
 };
 
 use crate::{
-    models::{CreateChat, CreateMessage},
+    models::{
+        ChatListItem, ChatTopic, CreateChat, CreateDirectMessage, CreateMessage, ListChats,
+        ListSidebar, SidebarItem, UpdateChatDescription, UpdateChatIcon, UpdateChatPolicy,
+        UpdateChatTopic,
+    },
     AppError, AppState, ErrorOutput,
 };
-use core_lib::{Chat, User};
+use core_lib::{Chat, ChatPolicy, User};
 
 #[utoipa::path(
     get,
 
     description = "Get Chats List",
     path = "/api/chats",
+    params(ListChats),
     responses(
-        (status = 200, description = "Get Chats List", body=Vec<Chat>)
+        (status = 200, description = "Get Chats List", body=Vec<ChatListItem>)
     ),security(
         (), // <-- make optional authentication
         ("token" = [])
@@ -27,8 +32,35 @@ use core_lib::{Chat, User};
 pub(crate) async fn list_chat_handler(
     Extension(user): Extension<User>,
     State(state): State<AppState>,
+    Query(input): Query<ListChats>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chats = state.fetch_all_chat(user.ws_id as _).await?;
+    let chats = state
+        .fetch_all_chat(user.ws_id as _, user.id as _, input)
+        .await?;
+    Ok((StatusCode::OK, Json(chats)))
+}
+#[utoipa::path(
+    get,
+
+    description = "Get chats of the user with last message, ordered by last activity",
+    path = "/api/chats/sidebar",
+    params(ListSidebar),
+    responses(
+        (status = 200, description = "Sidebar chats", body=Vec<SidebarItem>)
+    ),security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+
+)]
+pub(crate) async fn list_sidebar_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Query(input): Query<ListSidebar>,
+) -> Result<impl IntoResponse, AppError> {
+    let chats = state
+        .fetch_sidebar(user.ws_id as _, user.id as _, input)
+        .await?;
     Ok((StatusCode::OK, Json(chats)))
 }
 #[utoipa::path(
@@ -50,10 +82,41 @@ pub(crate) async fn create_chat_handler(
     State(state): State<AppState>,
     Json(input): Json<CreateChat>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chat = state.create_chat(input, user.ws_id as _).await?;
+    let chat = state
+        .create_chat(input, user.ws_id as _, user.id as _)
+        .await?;
     Ok((StatusCode::CREATED, Json(chat)))
 }
 
+#[utoipa::path(
+    post,
+    description = "Get or create a direct message with the given members",
+    path = "/api/chats/dm",
+    request_body = CreateDirectMessage,
+    responses(
+        (status = 200, description = "Existing direct message", body=Chat),
+        (status = 201, description = "Direct message created", body=Chat),
+        (status = 400, description = "Invalid members", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn get_or_create_dm_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Json(input): Json<CreateDirectMessage>,
+) -> Result<impl IntoResponse, AppError> {
+    let (chat, created) = state.get_or_create_dm(input, &user).await?;
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(chat)))
+}
+
 #[utoipa::path(
     get,
 
@@ -71,31 +134,238 @@ pub(crate) async fn create_chat_handler(
 
 )]
 pub(crate) async fn get_chat_handler(
+    Extension(user): Extension<User>,
     Path(id): Path<u64>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chat = state.get_chat_by_id(id as _).await?;
+    let chat = state.get_chat_by_id(id as _, user.ws_id as _).await?;
     match chat {
         Some(chat) => Ok((StatusCode::OK, Json(chat))),
         None => Err(AppError::NotFound(id.to_string())),
     }
 }
 pub(crate) async fn update_chat_handler(
+    Extension(user): Extension<User>,
     Path(id): Path<u64>,
     State(state): State<AppState>,
     Json(input): Json<CreateChat>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chat = state.update_chat(input, id as _).await?;
+    let chat = state
+        .update_chat(input, id as _, user.ws_id as _, user.id as _)
+        .await?;
     Ok((StatusCode::OK, Json(chat)))
 }
-pub(crate) async fn delete_chat_handler(
+
+#[utoipa::path(
+    put,
+    path = "/api/chats/{id}/topic",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateChatTopic,
+    responses(
+        (status = 200, description = "Chat topic updated", body=Chat),
+        (status = 400, description = "Invalid topic", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_chat_topic_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateChatTopic>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.update_chat_topic(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/topic",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Chat topic history", body=Vec<ChatTopic>)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_chat_topics_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let topics = state.list_chat_topics(id, user.ws_id as _).await?;
+    Ok((StatusCode::OK, Json(topics)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/chats/{id}/description",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateChatDescription,
+    responses(
+        (status = 200, description = "Chat description updated", body=Chat),
+        (status = 400, description = "Invalid description", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_chat_description_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateChatDescription>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state
+        .update_chat_description(input, id, user.ws_id as _)
+        .await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/policy",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Posting policy of the chat", body=ChatPolicy),
+        (status = 404, description = "Chat Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn get_chat_policy_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let policy = state.get_chat_policy(id, user.ws_id as _).await?;
+    Ok((StatusCode::OK, Json(policy)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/chats/{id}/policy",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateChatPolicy,
+    responses(
+        (status = 200, description = "Posting policy updated", body=ChatPolicy),
+        (status = 400, description = "Invalid policy", body=ErrorOutput),
+        (status = 403, description = "Not a chat moderator", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_chat_policy_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateChatPolicy>,
+) -> Result<impl IntoResponse, AppError> {
+    let policy = state.update_chat_policy(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(policy)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/chats/{id}/icon",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateChatIcon,
+    responses(
+        (status = 200, description = "Chat icon updated", body=Chat),
+        (status = 400, description = "Icon file not exists", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_chat_icon_handler(
+    Extension(user): Extension<User>,
     Path(id): Path<u64>,
     State(state): State<AppState>,
+    Json(input): Json<UpdateChatIcon>,
 ) -> Result<impl IntoResponse, AppError> {
-    let chat = state.delete_chat(id as _).await?;
+    let chat = state.update_chat_icon(input, id, user.ws_id as _).await?;
     Ok((StatusCode::OK, Json(chat)))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/chats/{id}/archive",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Chat archived", body=Chat),
+        (status = 403, description = "Chat already archived or not a moderator", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn archive_chat_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.archive_chat(id, &user).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/chats/{id}/archive",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Chat unarchived", body=Chat),
+        (status = 403, description = "Not a moderator", body=ErrorOutput),
+        (status = 404, description = "Archived Chat Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn unarchive_chat_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.unarchive_chat(id, &user).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/chats/{id}",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 202, description = "Chat deletion scheduled", body=Chat),
+        (status = 403, description = "Not a workspace admin", body=ErrorOutput),
+        (status = 404, description = "Chat Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn delete_chat_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.delete_chat(id, &user).await?;
+    Ok((StatusCode::ACCEPTED, Json(chat)))
+}
+
 pub(crate) async fn send_message_handler(
     Extension(user): Extension<User>,
     State(state): State<AppState>,
@@ -0,0 +1,172 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{AddGroupToChat, CreateUserGroup, UpdateUserGroup, UpdateUserGroupMembers, UserGroup},
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::{Chat, User};
+
+#[utoipa::path(
+    get,
+    path = "/api/groups",
+    responses(
+        (status = 200, description = "User groups of the workspace", body=Vec<UserGroup>)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_user_groups_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let groups = state.list_user_groups(user.ws_id as _).await?;
+    Ok((StatusCode::OK, Json(groups)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/groups",
+    request_body = CreateUserGroup,
+    responses(
+        (status = 201, description = "User group created", body=UserGroup),
+        (status = 400, description = "Invalid user group", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn create_user_group_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Json(input): Json<CreateUserGroup>,
+) -> Result<impl IntoResponse, AppError> {
+    let group = state.create_user_group(input, &user).await?;
+    Ok((StatusCode::CREATED, Json(group)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/groups/{id}",
+    params(("id"=u64, Path, description="User group ID")),
+    responses(
+        (status = 200, description = "User group", body=UserGroup),
+        (status = 404, description = "User group not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn get_user_group_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let group = state.get_user_group(id, user.ws_id as _).await?;
+    Ok((StatusCode::OK, Json(group)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/groups/{id}",
+    params(("id"=u64, Path, description="User group ID")),
+    request_body = UpdateUserGroup,
+    responses(
+        (status = 200, description = "User group updated", body=UserGroup),
+        (status = 400, description = "Invalid user group", body=ErrorOutput),
+        (status = 403, description = "Not the creator or workspace admin", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_user_group_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateUserGroup>,
+) -> Result<impl IntoResponse, AppError> {
+    let group = state.update_user_group(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(group)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/groups/{id}",
+    params(("id"=u64, Path, description="User group ID")),
+    responses(
+        (status = 204, description = "User group deleted"),
+        (status = 403, description = "Not the creator or workspace admin", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn delete_user_group_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.delete_user_group(id, &user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/groups/{id}/members",
+    params(("id"=u64, Path, description="User group ID")),
+    request_body = UpdateUserGroupMembers,
+    responses(
+        (status = 200, description = "Members of the group replaced", body=UserGroup),
+        (status = 400, description = "Some members do not exist", body=ErrorOutput),
+        (status = 403, description = "Not the creator or workspace admin", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_user_group_members_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateUserGroupMembers>,
+) -> Result<impl IntoResponse, AppError> {
+    let group = state.update_user_group_members(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(group)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/chats/{id}/groups",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = AddGroupToChat,
+    responses(
+        (status = 200, description = "Members of the group added to the chat", body=Chat),
+        (status = 400, description = "Group can't be added", body=ErrorOutput),
+        (status = 404, description = "User group not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn add_group_to_chat_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<AddGroupToChat>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.add_group_to_chat(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
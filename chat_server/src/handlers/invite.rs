@@ -0,0 +1,129 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{ChatAuditLog, ChatInvite, CreateChatInvite},
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::{Chat, User};
+
+#[utoipa::path(
+    post,
+    path = "/api/chats/{id}/invites",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = CreateChatInvite,
+    responses(
+        (status = 201, description = "Invite link created", body=ChatInvite),
+        (status = 400, description = "Invalid invite", body=ErrorOutput),
+        (status = 403, description = "Not a chat moderator", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn create_chat_invite_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<CreateChatInvite>,
+) -> Result<impl IntoResponse, AppError> {
+    let invite = state.create_chat_invite(input, id, &user).await?;
+    Ok((StatusCode::CREATED, Json(invite)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/invites",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Invite links of the chat", body=Vec<ChatInvite>),
+        (status = 403, description = "Not a chat moderator", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_chat_invites_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let invites = state.list_chat_invites(id, user.id as _).await?;
+    Ok((StatusCode::OK, Json(invites)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/invites/{id}",
+    params(("id"=u64, Path, description="Invite ID")),
+    responses(
+        (status = 200, description = "Invite link revoked", body=ChatInvite),
+        (status = 403, description = "Not a chat moderator", body=ErrorOutput),
+        (status = 404, description = "Invite not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn revoke_chat_invite_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let invite = state.revoke_chat_invite(id, &user).await?;
+    Ok((StatusCode::OK, Json(invite)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invites/{token}/redeem",
+    params(("token"=String, Path, description="Invite token")),
+    responses(
+        (status = 200, description = "Joined the chat", body=Chat),
+        (status = 403, description = "Invite belongs to another workspace", body=ErrorOutput),
+        (status = 404, description = "Invite not found", body=ErrorOutput),
+        (status = 410, description = "Invite expired, revoked or used up", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn redeem_chat_invite_handler(
+    Extension(user): Extension<User>,
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.redeem_chat_invite(&token, &user).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/audit",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Audit log of the chat, newest first", body=Vec<ChatAuditLog>),
+        (status = 403, description = "Not a chat moderator", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_chat_audit_logs_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.verify_chat_moderator(id, user.id as _).await?;
+    let logs = state.list_chat_audit_logs(id).await?;
+    Ok((StatusCode::OK, Json(logs)))
+}
@@ -0,0 +1,156 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{
+        ChatMember, ListChatMembers, MarkChatRead, MemberRole, NotifyPrefs, UpdateChatSidebar,
+        UpdateMemberRole, UpdateNotifyPrefs,
+    },
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::{ChatSidebarPrefs, ReadMarker, User};
+
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/notifications",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Notification preferences of current user", body=NotifyPrefs),
+        (status = 404, description = "Not a member of the chat", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn get_notify_prefs_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let prefs = state.get_notify_prefs(id, user.id as _).await?;
+    Ok((StatusCode::OK, Json(prefs)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/chats/{id}/notifications",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateNotifyPrefs,
+    responses(
+        (status = 200, description = "Notification preferences updated", body=NotifyPrefs),
+        (status = 404, description = "Not a member of the chat", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_notify_prefs_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateNotifyPrefs>,
+) -> Result<impl IntoResponse, AppError> {
+    let prefs = state.update_notify_prefs(input, id, user.id as _).await?;
+    Ok((StatusCode::OK, Json(prefs)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/chats/{id}/read",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = MarkChatRead,
+    responses(
+        (status = 200, description = "Read marker of current user", body=ReadMarker),
+        (status = 404, description = "Message Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn mark_chat_read_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<MarkChatRead>,
+) -> Result<impl IntoResponse, AppError> {
+    let marker = state.mark_chat_read(input, id, user.id as _).await?;
+    Ok((StatusCode::OK, Json(marker)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/chats/{id}/sidebar",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateChatSidebar,
+    responses(
+        (status = 200, description = "Sidebar state of the chat for current user", body=ChatSidebarPrefs),
+        (status = 404, description = "Section Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_chat_sidebar_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateChatSidebar>,
+) -> Result<impl IntoResponse, AppError> {
+    let prefs = state.update_chat_sidebar(input, id, user.id as _).await?;
+    Ok((StatusCode::OK, Json(prefs)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/chats/{id}/roles",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateMemberRole,
+    responses(
+        (status = 200, description = "Role of the member updated", body=MemberRole),
+        (status = 403, description = "Not a chat moderator", body=ErrorOutput),
+        (status = 404, description = "Not a member of the chat", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_member_role_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateMemberRole>,
+) -> Result<impl IntoResponse, AppError> {
+    let role = state.update_member_role(input, id, user.id as _).await?;
+    Ok((StatusCode::OK, Json(role)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/members",
+    params(("id"=u64, Path, description="Chat ID"), ListChatMembers),
+    responses(
+        (status = 200, description = "Members of the chat ordered by user id", body=Vec<ChatMember>),
+        (status = 404, description = "Chat Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_chat_members_handler(
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Query(input): Query<ListChatMembers>,
+) -> Result<impl IntoResponse, AppError> {
+    let members = state.list_chat_members(input, id).await?;
+    Ok((StatusCode::OK, Json(members)))
+}
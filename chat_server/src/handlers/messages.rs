@@ -9,7 +9,13 @@ use tokio::fs;
 
 use tracing::{info, warn};
 
-use crate::{models::ListMessages, AppError, AppState, ChatFile, ErrorOutput};
+use crate::{
+    models::{
+        CreateMessage, ListMessages, ListMessagesSince, MessageRevision, MessagesSince,
+        UpdateMessage,
+    },
+    AppError, AppState, ChatFile, ErrorOutput,
+};
 use core_lib::{Message, User};
 #[utoipa::path(
     get,
@@ -28,21 +34,232 @@ use core_lib::{Message, User};
 
 )]
 pub(crate) async fn list_messages_handler(
-    Extension(_user): Extension<User>,
+    Extension(user): Extension<User>,
     State(state): State<AppState>,
     Path(id): Path<i64>,
     Query(input): Query<ListMessages>,
 ) -> Result<impl IntoResponse, AppError> {
-    let messages = state.list_messages(input, id as _).await?;
+    let messages = state.list_messages(input, id as _, &user).await?;
     Ok(Json(messages))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/messages/since",
+    params(("id"=u64, Path, description="Chat ID"),ListMessagesSince),
+    responses(
+        (status = 200, description = "Messages after the seq, oldest first", body=MessagesSince),
+        (status = 404, description = "Chat not found", body=ErrorOutput),
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_messages_since_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+    Query(input): Query<ListMessagesSince>,
+) -> Result<impl IntoResponse, AppError> {
+    let ret = state.list_messages_since(input, id, &user).await?;
+    Ok(Json(ret))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/messages/{id}",
+    params(("id"=u64, Path, description="Message ID")),
+    request_body = UpdateMessage,
+    responses(
+        (status = 200, description = "Message edited", body=Message),
+        (status = 400, description = "Invalid content", body=ErrorOutput),
+        (status = 403, description = "Not the author or edit window has passed", body=ErrorOutput),
+        (status = 404, description = "Message not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_message_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateMessage>,
+) -> Result<impl IntoResponse, AppError> {
+    let message = state.update_message(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(message)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/messages/{id}",
+    params(("id"=u64, Path, description="Message ID")),
+    responses(
+        (status = 200, description = "Message deleted, its tombstone is returned", body=Message),
+        (status = 403, description = "Not the author or a chat moderator", body=ErrorOutput),
+        (status = 404, description = "Message not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn delete_message_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let message = state.delete_message(id, &user).await?;
+    Ok((StatusCode::OK, Json(message)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/messages/{id}/revisions",
+    params(("id"=u64, Path, description="Message ID")),
+    responses(
+        (status = 200, description = "Previous versions of the message, oldest first", body=Vec<MessageRevision>),
+        (status = 403, description = "Not a chat moderator", body=ErrorOutput),
+        (status = 404, description = "Message not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_message_revisions_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let revisions = state.list_message_revisions(id, &user).await?;
+    Ok((StatusCode::OK, Json(revisions)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/messages/{id}/replies",
+    params(("id"=u64, Path, description="Root message ID"),ListMessages),
+    responses(
+        (status = 200, description = "Replies of the thread, newest first", body=Vec<Message>),
+        (status = 404, description = "Thread not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_replies_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Query(input): Query<ListMessages>,
+) -> Result<impl IntoResponse, AppError> {
+    let replies = state.list_replies(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(replies)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/messages/{id}/replies/since",
+    params(("id"=u64, Path, description="Root message ID"),ListMessagesSince),
+    responses(
+        (status = 200, description = "Replies after the seq, oldest first", body=MessagesSince),
+        (status = 404, description = "Thread not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_replies_since_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Query(input): Query<ListMessagesSince>,
+) -> Result<impl IntoResponse, AppError> {
+    let ret = state.list_replies_since(input, id, &user).await?;
+    Ok(Json(ret))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/messages/{id}/replies",
+    params(("id"=u64, Path, description="Root message ID")),
+    request_body = CreateMessage,
+    responses(
+        (status = 201, description = "Reply sent", body=Message),
+        (status = 400, description = "Invalid reply", body=ErrorOutput),
+        (status = 404, description = "Thread not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn create_reply_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<CreateMessage>,
+) -> Result<impl IntoResponse, AppError> {
+    let reply = state.create_reply(input, id, &user).await?;
+    Ok((StatusCode::CREATED, Json(reply)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/messages/{id}/follow",
+    params(("id"=u64, Path, description="Root message ID")),
+    responses(
+        (status = 204, description = "Thread followed"),
+        (status = 404, description = "Thread not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn follow_thread_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.follow_thread(id, &user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/messages/{id}/follow",
+    params(("id"=u64, Path, description="Root message ID")),
+    responses(
+        (status = 204, description = "Thread unfollowed"),
+        (status = 404, description = "Thread not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn unfollow_thread_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.unfollow_thread(id, &user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub(crate) async fn download_file_handler(
     Extension(user): Extension<User>,
     Path((ws_id, path)): Path<(i64, String)>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
-    if user.ws_id != ws_id {
+    let url = format!("/files/{}/{}", ws_id, path);
+    if !state.can_access_file(&url, &user).await? {
         return Err(AppError::NotFound(
             "File doesn't exist or you dont have permission".to_string(),
         ));
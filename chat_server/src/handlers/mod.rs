@@ -1,12 +1,30 @@
 mod auth;
+mod block;
 mod chat;
+mod group;
+mod invite;
+mod member;
 mod messages;
+mod pin;
+mod reaction;
+mod search;
+mod section;
+mod shared;
 mod workspace;
 
 pub(crate) use auth::*;
 use axum::response::IntoResponse;
+pub(crate) use block::*;
 pub(crate) use chat::*;
+pub(crate) use group::*;
+pub(crate) use invite::*;
+pub(crate) use member::*;
 pub(crate) use messages::*;
+pub(crate) use pin::*;
+pub(crate) use reaction::*;
+pub(crate) use search::*;
+pub(crate) use section::*;
+pub(crate) use shared::*;
 pub(crate) use workspace::*;
 pub(crate) async fn index_handler() -> impl IntoResponse {
     "index"
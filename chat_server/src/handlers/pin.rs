@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{models::PinnedMessage, AppError, AppState, ErrorOutput};
+use core_lib::{MessagePin, User};
+
+#[utoipa::path(
+    get,
+    path = "/api/chats/{id}/pins",
+    params(("id"=u64, Path, description="Chat ID")),
+    responses(
+        (status = 200, description = "Pinned messages of the chat with their pinners, latest first", body=Vec<PinnedMessage>)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_pinned_messages_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let pins = state.list_pinned_messages(id, &user).await?;
+    Ok((StatusCode::OK, Json(pins)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/messages/{id}/pin",
+    params(("id"=u64, Path, description="Message ID")),
+    responses(
+        (status = 200, description = "Message pinned", body=MessagePin),
+        (status = 400, description = "Too many pinned messages", body=ErrorOutput),
+        (status = 403, description = "Not allowed by the pin policy", body=ErrorOutput),
+        (status = 404, description = "Message not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn pin_message_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let pin = state.pin_message(id, &user).await?;
+    Ok((StatusCode::OK, Json(pin)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/messages/{id}/pin",
+    params(("id"=u64, Path, description="Message ID")),
+    responses(
+        (status = 204, description = "Message unpinned"),
+        (status = 403, description = "Not allowed by the pin policy", body=ErrorOutput),
+        (status = 404, description = "Message not pinned", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn unpin_message_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.unpin_message(id, &user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,126 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{AddReaction, CreateWorkspaceEmoji, WorkspaceEmoji},
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::{Reaction, User};
+
+#[utoipa::path(
+    get,
+    path = "/api/emojis",
+    responses(
+        (status = 200, description = "Custom emoji of the workspace", body=Vec<WorkspaceEmoji>)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_workspace_emojis_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let emojis = state.list_workspace_emojis(user.ws_id as _).await?;
+    Ok((StatusCode::OK, Json(emojis)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/emojis",
+    request_body = CreateWorkspaceEmoji,
+    responses(
+        (status = 201, description = "Custom emoji created", body=WorkspaceEmoji),
+        (status = 400, description = "Invalid emoji", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn create_workspace_emoji_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Json(input): Json<CreateWorkspaceEmoji>,
+) -> Result<impl IntoResponse, AppError> {
+    let emoji = state.create_workspace_emoji(input, &user).await?;
+    Ok((StatusCode::CREATED, Json(emoji)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/emojis/{id}",
+    params(("id"=u64, Path, description="Emoji ID")),
+    responses(
+        (status = 204, description = "Custom emoji deleted"),
+        (status = 403, description = "Not the creator or workspace admin", body=ErrorOutput),
+        (status = 404, description = "Emoji not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn delete_workspace_emoji_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.delete_workspace_emoji(id, &user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/messages/{id}/reactions",
+    params(("id"=u64, Path, description="Message ID")),
+    request_body = AddReaction,
+    responses(
+        (status = 200, description = "Reactions of the message", body=Vec<Reaction>),
+        (status = 400, description = "Invalid emoji", body=ErrorOutput),
+        (status = 404, description = "Message not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn add_reaction_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<AddReaction>,
+) -> Result<impl IntoResponse, AppError> {
+    let reactions = state.add_reaction(input, id, &user).await?;
+    Ok((StatusCode::OK, Json(reactions)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/messages/{id}/reactions/{emoji}",
+    params(
+        ("id"=u64, Path, description="Message ID"),
+        ("emoji"=String, Path, description="Unicode emoji or :name: of a custom emoji")
+    ),
+    responses(
+        (status = 200, description = "Reactions of the message", body=Vec<Reaction>),
+        (status = 404, description = "Reaction not found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn remove_reaction_handler(
+    Extension(user): Extension<User>,
+    Path((id, emoji)): Path<(u64, String)>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let reactions = state.remove_reaction(&emoji, id, &user).await?;
+    Ok((StatusCode::OK, Json(reactions)))
+}
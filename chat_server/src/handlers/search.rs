@@ -0,0 +1,34 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{SearchMessages, SearchResult},
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::User;
+
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    params(SearchMessages),
+    responses(
+        (status = 200, description = "Messages matching the query in chats of the user", body=SearchResult),
+        (status = 400, description = "Invalid query", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn search_messages_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Query(input): Query<SearchMessages>,
+) -> Result<impl IntoResponse, AppError> {
+    let ret = state.search_messages(input, &user).await?;
+    Ok((StatusCode::OK, Json(ret)))
+}
@@ -0,0 +1,123 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{CreateSidebarSection, ReorderSidebarSections},
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::{SidebarSection, User};
+
+#[utoipa::path(
+    get,
+    path = "/api/sections",
+    responses(
+        (status = 200, description = "Sidebar sections of current user in order", body=Vec<SidebarSection>)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_sidebar_sections_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let sections = state.list_sidebar_sections(user.id as _).await?;
+    Ok((StatusCode::OK, Json(sections)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/sections",
+    request_body = CreateSidebarSection,
+    responses(
+        (status = 201, description = "Sidebar section created", body=SidebarSection),
+        (status = 400, description = "Invalid or duplicated name", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn create_sidebar_section_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Json(input): Json<CreateSidebarSection>,
+) -> Result<impl IntoResponse, AppError> {
+    let section = state.create_sidebar_section(input, user.id as _).await?;
+    Ok((StatusCode::CREATED, Json(section)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/sections/{id}",
+    params(("id"=u64, Path, description="Section ID")),
+    request_body = CreateSidebarSection,
+    responses(
+        (status = 200, description = "Sidebar section renamed", body=SidebarSection),
+        (status = 404, description = "Section Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn rename_sidebar_section_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<CreateSidebarSection>,
+) -> Result<impl IntoResponse, AppError> {
+    let section = state
+        .rename_sidebar_section(input, id, user.id as _)
+        .await?;
+    Ok((StatusCode::OK, Json(section)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/sections/{id}",
+    params(("id"=u64, Path, description="Section ID")),
+    responses(
+        (status = 204, description = "Sidebar section deleted"),
+        (status = 404, description = "Section Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn delete_sidebar_section_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    state.delete_sidebar_section(id, user.id as _).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/sections/order",
+    request_body = ReorderSidebarSections,
+    responses(
+        (status = 200, description = "Sidebar sections in the new order", body=Vec<SidebarSection>),
+        (status = 400, description = "Ids don't match the sections", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn reorder_sidebar_sections_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Json(input): Json<ReorderSidebarSections>,
+) -> Result<impl IntoResponse, AppError> {
+    let sections = state.reorder_sidebar_sections(input, user.id as _).await?;
+    Ok((StatusCode::OK, Json(sections)))
+}
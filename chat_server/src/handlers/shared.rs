@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+
+use crate::{
+    models::{DisconnectSharedChannel, ShareChat, SharedChannel, UpdateSharedMembers},
+    AppError, AppState, ErrorOutput,
+};
+use core_lib::{Chat, User};
+
+#[utoipa::path(
+    get,
+    path = "/api/shared_channels",
+    responses(
+        (status = 200, description = "Channels shared by or with current workspace", body=Vec<SharedChannel>)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn list_shared_channels_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let shares = state.list_shared_channels(user.ws_id as _).await?;
+    Ok((StatusCode::OK, Json(shares)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/shared_channels",
+    request_body = ShareChat,
+    responses(
+        (status = 201, description = "Workspace invited to the channel", body=SharedChannel),
+        (status = 400, description = "Chat can't be shared", body=ErrorOutput),
+        (status = 403, description = "Not a workspace admin", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn share_chat_handler(
+    Extension(user): Extension<User>,
+    State(state): State<AppState>,
+    Json(input): Json<ShareChat>,
+) -> Result<impl IntoResponse, AppError> {
+    let share = state.share_chat(input, &user).await?;
+    Ok((StatusCode::CREATED, Json(share)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/shared_channels/{id}/accept",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateSharedMembers,
+    responses(
+        (status = 200, description = "Invitation accepted", body=Chat),
+        (status = 403, description = "Not a workspace admin", body=ErrorOutput),
+        (status = 404, description = "No pending invitation", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn accept_shared_channel_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateSharedMembers>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.accept_shared_channel(id, input, &user).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/shared_channels/{id}/members",
+    params(("id"=u64, Path, description="Chat ID")),
+    request_body = UpdateSharedMembers,
+    responses(
+        (status = 200, description = "Members of current workspace updated", body=Chat),
+        (status = 403, description = "Not a workspace admin", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn update_shared_members_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Json(input): Json<UpdateSharedMembers>,
+) -> Result<impl IntoResponse, AppError> {
+    let chat = state.update_shared_members(id, input, &user).await?;
+    Ok((StatusCode::OK, Json(chat)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/shared_channels/{id}",
+    params(("id"=u64, Path, description="Chat ID"), DisconnectSharedChannel),
+    responses(
+        (status = 204, description = "Shared channel disconnected"),
+        (status = 403, description = "Not a workspace admin", body=ErrorOutput),
+        (status = 404, description = "Shared channel Not Found", body=ErrorOutput)
+    ),
+    security(
+        (), // <-- make optional authentication
+        ("token" = [])
+    )
+)]
+pub(crate) async fn disconnect_shared_channel_handler(
+    Extension(user): Extension<User>,
+    Path(id): Path<u64>,
+    State(state): State<AppState>,
+    Query(input): Query<DisconnectSharedChannel>,
+) -> Result<impl IntoResponse, AppError> {
+    state.disconnect_shared_channel(id, input, &user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
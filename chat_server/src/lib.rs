@@ -12,7 +12,7 @@ use tokio::fs;
 mod models;
 use axum::{
     middleware::from_fn_with_state,
-    routing::{get, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 pub use error::{AppError, ErrorOutput};
@@ -25,7 +25,6 @@ pub struct AppState {
     inner: Arc<AppStateInner>,
 }
 #[allow(unused)]
-
 pub struct AppStateInner {
     pub(crate) config: AppConfig,
     pub(crate) dk: DecodingKey,
@@ -47,16 +46,131 @@ pub async fn get_router(state: AppState) -> Result<Router, AppError> {
             "/:id",
             get(get_chat_handler)
                 .patch(update_chat_handler)
-                .post(send_message_handler)
-                .delete(delete_chat_handler),
+                .post(send_message_handler),
+        )
+        .route(
+            "/:id/archive",
+            post(archive_chat_handler).delete(unarchive_chat_handler),
         )
+        .route(
+            "/:id/topic",
+            get(list_chat_topics_handler).put(update_chat_topic_handler),
+        )
+        .route("/:id/description", put(update_chat_description_handler))
+        .route("/:id/icon", put(update_chat_icon_handler))
+        .route(
+            "/:id/policy",
+            get(get_chat_policy_handler).put(update_chat_policy_handler),
+        )
+        .route(
+            "/:id/notifications",
+            get(get_notify_prefs_handler).put(update_notify_prefs_handler),
+        )
+        .route("/:id/read", post(mark_chat_read_handler))
+        .route("/:id/sidebar", put(update_chat_sidebar_handler))
         .route("/:id/messages", get(list_messages_handler))
+        .route("/:id/messages/since", get(list_messages_since_handler))
+        .route(
+            "/:id/invites",
+            get(list_chat_invites_handler).post(create_chat_invite_handler),
+        )
+        .route("/:id/members", get(list_chat_members_handler))
+        .route("/:id/groups", post(add_group_to_chat_handler))
+        .route("/:id/roles", put(update_member_role_handler))
+        .route("/:id/audit", get(list_chat_audit_logs_handler))
+        .route("/:id/pins", get(list_pinned_messages_handler))
         .layer(from_fn_with_state(state.clone(), verify_chat))
+        // workspace admins may delete chats they are not a member of
+        .route("/:id", delete(delete_chat_handler))
+        .route("/dm", post(get_or_create_dm_handler))
+        .route("/sidebar", get(list_sidebar_handler))
         .route("/", get(list_chat_handler).post(create_chat_handler));
 
+    let section = Router::new()
+        .route(
+            "/",
+            get(list_sidebar_sections_handler).post(create_sidebar_section_handler),
+        )
+        .route("/order", put(reorder_sidebar_sections_handler))
+        .route(
+            "/:id",
+            patch(rename_sidebar_section_handler).delete(delete_sidebar_section_handler),
+        );
+
+    let shared = Router::new()
+        .route(
+            "/",
+            get(list_shared_channels_handler).post(share_chat_handler),
+        )
+        .route("/:id", delete(disconnect_shared_channel_handler))
+        .route("/:id/accept", post(accept_shared_channel_handler))
+        .route("/:id/members", put(update_shared_members_handler));
+
+    let invite = Router::new()
+        .route("/:id", delete(revoke_chat_invite_handler))
+        .route("/:token/redeem", post(redeem_chat_invite_handler));
+
+    let block = Router::new()
+        .route(
+            "/",
+            get(list_blocked_users_handler).post(block_user_handler),
+        )
+        .route("/reports", get(list_block_reports_handler))
+        .route("/:id", delete(unblock_user_handler));
+
+    let group = Router::new()
+        .route(
+            "/",
+            get(list_user_groups_handler).post(create_user_group_handler),
+        )
+        .route(
+            "/:id",
+            get(get_user_group_handler)
+                .patch(update_user_group_handler)
+                .delete(delete_user_group_handler),
+        )
+        .route("/:id/members", put(update_user_group_members_handler));
+
+    let message = Router::new()
+        .route(
+            "/:id",
+            patch(update_message_handler).delete(delete_message_handler),
+        )
+        .route("/:id/revisions", get(list_message_revisions_handler))
+        .route(
+            "/:id/replies",
+            get(list_replies_handler).post(create_reply_handler),
+        )
+        .route("/:id/replies/since", get(list_replies_since_handler))
+        .route(
+            "/:id/follow",
+            post(follow_thread_handler).delete(unfollow_thread_handler),
+        )
+        .route("/:id/reactions", post(add_reaction_handler))
+        .route("/:id/reactions/:emoji", delete(remove_reaction_handler))
+        .route(
+            "/:id/pin",
+            post(pin_message_handler).delete(unpin_message_handler),
+        );
+
+    let emoji = Router::new()
+        .route(
+            "/",
+            get(list_workspace_emojis_handler).post(create_workspace_emoji_handler),
+        )
+        .route("/:id", delete(delete_workspace_emoji_handler));
+
     let api = Router::new()
         .route("/users", get(list_chat_users_handler))
+        .route("/search", get(search_messages_handler))
         .nest("/chats", chat)
+        .nest("/sections", section)
+        .nest("/shared_channels", shared)
+        .nest("/invites", invite)
+        .nest("/blocks", block)
+        .nest("/groups", group)
+        .nest("/messages", message)
+        .nest("/emojis", emoji)
         .route("/upload", post(upload_handler))
         .route("/files/:ws_id/*path", get(download_file_handler))
         .layer(from_fn_with_state(state.clone(), verify_token::<AppState>))
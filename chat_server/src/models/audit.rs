@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, ToSchema, Deserialize, Serialize)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    InviteCreated,
+    InviteRevoked,
+    InviteRedeemed,
+    RoleChanged,
+    PolicyChanged,
+    MessageDeleted,
+    MessagePinned,
+    MessageUnpinned,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct ChatAuditLog {
+    pub id: i64,
+    pub chat_id: i64,
+    //user performing the action
+    pub user_id: i64,
+    pub action: AuditAction,
+    #[schema(value_type = Object)]
+    pub detail: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AppState {
+    pub async fn list_chat_audit_logs(&self, chat_id: u64) -> Result<Vec<ChatAuditLog>, AppError> {
+        let logs = sqlx::query_as(
+            r#"
+            SELECT id,chat_id,user_id,action,detail,created_at
+            FROM chat_audit_logs
+            WHERE chat_id=$1
+            ORDER BY created_at DESC, id DESC
+            "#,
+        )
+        .bind(chat_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(logs)
+    }
+}
+
+//written in the transaction of the audited change
+pub(crate) async fn write_audit_log(
+    conn: &mut PgConnection,
+    chat_id: i64,
+    user_id: i64,
+    action: AuditAction,
+    detail: serde_json::Value,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO chat_audit_logs(chat_id,user_id,action,detail)
+        VALUES($1,$2,$3,$4)
+        "#,
+    )
+    .bind(chat_id)
+    .bind(user_id)
+    .bind(action)
+    .bind(detail)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use chrono::{DateTime, Utc};
+use core_lib::{ChatUser, User};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+const MAX_REASON_LEN: usize = 256;
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct BlockUser {
+    pub user_id: i64,
+    //visible to workspace admins, never together with the blocker
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct BlockedUser {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub user: ChatUser,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+//blocks received by a user, for moderation by workspace admins
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct BlockReport {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub user: ChatUser,
+    pub block_count: i64,
+    pub reasons: Vec<String>,
+    pub last_blocked_at: DateTime<Utc>,
+}
+
+impl AppState {
+    pub async fn block_user(&self, input: BlockUser, user: &User) -> Result<BlockedUser, AppError> {
+        if input.user_id == user.id {
+            return Err(AppError::BlockError("can't block yourself".to_string()));
+        }
+        if input
+            .reason
+            .as_ref()
+            .is_some_and(|r| r.chars().count() > MAX_REASON_LEN)
+        {
+            return Err(AppError::BlockError(format!(
+                "reason must be at most {} characters",
+                MAX_REASON_LEN
+            )));
+        }
+        if self
+            .fetch_chat_user_by_ids(&[input.user_id], user.ws_id as _)
+            .await?
+            .is_empty()
+        {
+            return Err(AppError::NotFound(format!("user {}", input.user_id)));
+        }
+        let blocked = sqlx::query_as(
+            r#"
+            WITH b AS (
+                INSERT INTO user_blocks(blocker_id,blocked_id,reason)
+                VALUES($1,$2,$3)
+                ON CONFLICT(blocker_id,blocked_id) DO UPDATE SET reason=EXCLUDED.reason
+                RETURNING blocked_id,reason,created_at
+            )
+            SELECT u.id,u.fullname,u.email,b.reason,b.created_at
+            FROM b
+            JOIN users u ON u.id=b.blocked_id
+            "#,
+        )
+        .bind(user.id)
+        .bind(input.user_id)
+        .bind(input.reason)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(blocked)
+    }
+
+    pub async fn unblock_user(&self, blocked_id: u64, user_id: u64) -> Result<(), AppError> {
+        let ret = sqlx::query(r#"DELETE FROM user_blocks WHERE blocker_id=$1 and blocked_id=$2"#)
+            .bind(user_id as i64)
+            .bind(blocked_id as i64)
+            .execute(&self.pool)
+            .await?;
+        if ret.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("block of user {}", blocked_id)));
+        }
+        Ok(())
+    }
+
+    //only the blocker can see the users they blocked
+    pub async fn list_blocked_users(&self, user_id: u64) -> Result<Vec<BlockedUser>, AppError> {
+        let users = sqlx::query_as(
+            r#"
+            SELECT u.id,u.fullname,u.email,b.reason,b.created_at
+            FROM user_blocks b
+            JOIN users u ON u.id=b.blocked_id
+            WHERE b.blocker_id=$1
+            ORDER BY b.created_at DESC
+            "#,
+        )
+        .bind(user_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(users)
+    }
+
+    pub async fn list_block_reports(&self, user: &User) -> Result<Vec<BlockReport>, AppError> {
+        if !self
+            .is_workspace_admin(user.ws_id as _, user.id as _)
+            .await?
+        {
+            return Err(AppError::PermissionDenied(
+                "only workspace admin can see block reports".to_string(),
+            ));
+        }
+        let reports = sqlx::query_as(
+            r#"
+            SELECT u.id,u.fullname,u.email,
+                count(*) AS block_count,
+                coalesce(array_agg(b.reason) FILTER (WHERE b.reason IS NOT NULL),'{}') AS reasons,
+                max(b.created_at) AS last_blocked_at
+            FROM user_blocks b
+            JOIN users u ON u.id=b.blocked_id
+            WHERE u.ws_id=$1
+            GROUP BY u.id
+            ORDER BY block_count DESC, last_blocked_at DESC
+            "#,
+        )
+        .bind(user.ws_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(reports)
+    }
+
+    //blocked users can't add the blocker to chats, a direct message is refused
+    //whichever side blocked the other
+    pub(crate) async fn verify_not_blocked(
+        &self,
+        user_id: i64,
+        members: &[i64],
+        both_ways: bool,
+    ) -> Result<(), AppError> {
+        let blocked: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM user_blocks
+                WHERE (blocker_id=ANY($2) and blocked_id=$1)
+                    OR ($3 and blocker_id=$1 and blocked_id=ANY($2))
+            )
+            "#,
+        )
+        .bind(user_id)
+        .bind(members)
+        .bind(both_ways)
+        .fetch_one(&self.pool)
+        .await?;
+        if blocked {
+            return Err(AppError::PermissionDenied(
+                "some members can't be added to a chat with you".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateChat, CreateDirectMessage, CreateMessage, ListChats, ListMessages};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_block_should_prevent_dm_adds_and_hide_messages() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin3 = state
+            .find_user_by_email("kevin3.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let alice = state.find_user_by_email("alice@acme.org").await?.unwrap();
+
+        //kevin3 blocks kevin2
+        let input = BlockUser {
+            user_id: 2,
+            reason: Some("spam".to_string()),
+        };
+        state.block_user(input, &kevin3).await?;
+        let input = BlockUser {
+            user_id: 4,
+            reason: None,
+        };
+        let ret = state.block_user(input, &kevin3).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        //kevin2 can't open a DM with kevin3 or add kevin3 to a group
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let ret = state
+            .get_or_create_dm(CreateDirectMessage { members: vec![3] }, &kevin2)
+            .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let ret = state
+            .get_or_create_dm(CreateDirectMessage { members: vec![2] }, &kevin3)
+            .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let ret = state
+            .create_chat(CreateChat::new("", &[1, 2, 3], false), 1, 2)
+            .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        state
+            .create_chat(CreateChat::new("", &[1, 2, 3], false), 1, 1)
+            .await?;
+
+        //messages of kevin2 are hidden from kevin3 only
+        let input = CreateMessage {
+            content: "hello @kevin3".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        state.create_message(input, 2, 2).await?;
+        let list = ListMessages::default();
+        assert!(state
+            .list_messages(list.clone(), 2, &kevin3)
+            .await?
+            .is_empty());
+        assert_eq!(state.list_messages(list, 2, &kevin).await?.len(), 1);
+        //the chat list counts like the sidebar
+        let unread = |user_id: u64| {
+            let state = &state;
+            async move {
+                let chats = state
+                    .fetch_all_chat(1, user_id, ListChats::default())
+                    .await?;
+                let general = chats.into_iter().find(|c| c.chat.id == 2).unwrap();
+                Ok::<_, AppError>((general.unread_count, general.mention_count))
+            }
+        };
+        assert_eq!(unread(3).await?, (0, 0));
+        assert_eq!(unread(1).await?, (1, 0));
+
+        assert_eq!(state.list_blocked_users(3).await?.len(), 1);
+        assert!(state.list_blocked_users(1).await?.is_empty());
+        let reports = state.list_block_reports(&kevin).await?;
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].user.id, 2);
+        assert_eq!(reports[0].reasons, vec!["spam".to_string()]);
+        assert!(state.list_block_reports(&kevin3).await.is_err());
+        assert!(state.list_block_reports(&alice).await.is_err());
+
+        state.unblock_user(2, 3).await?;
+        assert!(state.unblock_user(2, 3).await.is_err());
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use crate::{AppError, AppState};
-use core_lib::{Chat, ChatType};
+use core_lib::{Chat, ChatType, User};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use sqlx::FromRow;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
 pub struct CreateChat {
@@ -10,90 +12,314 @@ pub struct CreateChat {
     pub public: bool,
 }
 
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct CreateDirectMessage {
+    //other members of the direct message, the current user is always included
+    pub members: Vec<i64>,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct ChatListItem {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub chat: Chat,
+    //unread messages of current user outside threads, 0 if not a member
+    pub unread_count: i64,
+    pub mention_count: i64,
+}
+
+#[derive(Debug, Clone, Default, IntoParams, Deserialize, Serialize)]
+pub struct ListChats {
+    //list archived chats instead of active ones
+    #[serde(default)]
+    pub archived: bool,
+}
+
 impl AppState {
-    pub async fn create_chat(&self, input: CreateChat, ws_id: u64) -> Result<Chat, AppError> {
-        let pool = &self.pool;
+    //the creator becomes the first moderator of the chat
+    pub async fn create_chat(
+        &self,
+        input: CreateChat,
+        ws_id: u64,
+        user_id: u64,
+    ) -> Result<Chat, AppError> {
         //对话成员必须大于2人
-        let chat_type = self.verify_chat_type(&input).await?;
+        let chat_type = self.verify_chat_type(&input, ws_id, None).await?;
+        self.verify_not_blocked(user_id as _, &input.members, false)
+            .await?;
+        let (chat, _) = self.insert_chat(input, chat_type, ws_id, user_id).await?;
+        Ok(chat)
+    }
+
+    //direct messages are unique per member set, return the existing one if any
+    pub async fn get_or_create_dm(
+        &self,
+        input: CreateDirectMessage,
+        user: &User,
+    ) -> Result<(Chat, bool), AppError> {
+        let mut members = input.members;
+        members.push(user.id);
+        members.sort_unstable();
+        members.dedup();
+        let input = CreateChat {
+            name: None,
+            members,
+            public: false,
+        };
+        let chat_type = self.verify_chat_type(&input, user.ws_id as _, None).await?;
+        self.verify_not_blocked(user.id, &input.members, true)
+            .await?;
+        self.insert_chat(input, chat_type, user.ws_id as _, user.id as _)
+            .await
+    }
+
+    async fn insert_chat(
+        &self,
+        input: CreateChat,
+        chat_type: ChatType,
+        ws_id: u64,
+        created_by: u64,
+    ) -> Result<(Chat, bool), AppError> {
+        let pool = &self.pool;
+        let chat: Option<Chat> = sqlx::query_as(
+            r#"
+            INSERT INTO chats(ws_id,name,type,members,created_by)
+            VALUES($1,$2,$3,$4,$5)
+            ON CONFLICT (ws_id,dm_key) WHERE dm_key IS NOT NULL DO NOTHING
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            "#,
+        )
+        .bind(ws_id as i64)
+        .bind(&input.name)
+        .bind(&chat_type)
+        .bind(&input.members)
+        .bind(created_by as i64)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(chat) = chat {
+            return Ok((chat, true));
+        }
 
         let chat = sqlx::query_as(
             r#"
-            INSERT INTO chats(ws_id,name,type,members)
-            VALUES($1,$2,$3,$4)
-            RETURNING id,ws_id,name,type,members,created_at
+            SELECT id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            FROM chats
+            WHERE ws_id=$1 and dm_key=chat_dm_key($2,$3)
             "#,
         )
         .bind(ws_id as i64)
-        .bind(input.name)
         .bind(chat_type)
         .bind(input.members)
         .fetch_one(pool)
         .await?;
-        Ok(chat)
+        Ok((chat, false))
     }
 
-    pub async fn fetch_all_chat(&self, ws_id: u64) -> Result<Vec<Chat>, AppError> {
+    pub async fn fetch_all_chat(
+        &self,
+        ws_id: u64,
+        user_id: u64,
+        input: ListChats,
+    ) -> Result<Vec<ChatListItem>, AppError> {
         let pool = &self.pool;
-        let chats = sqlx::query_as(
+        let chats: Vec<ChatListItem> = sqlx::query_as(
             r#"
-            SELECT id,ws_id,name,type,members,created_at
-            FROM chats
-            WHERE ws_id=$1 order by created_at desc
+            SELECT c.id,c.ws_id,c.name,c.type,c.members,c.created_at,c.archived_at,c.description,c.topic,c.icon,c.member_count,
+                coalesce(r.unread_count,0) AS unread_count,
+                coalesce(r.mention_count,0) AS mention_count
+            FROM chats c
+            LEFT JOIN LATERAL (
+                SELECT count(*) AS unread_count,
+                    count(*) FILTER (
+                        WHERE EXISTS(
+                            SELECT 1 FROM message_mentions mm
+                            WHERE mm.message_id=m.id and mm.user_id=cm.user_id
+                        )
+                    ) AS mention_count
+                FROM chat_members cm
+                JOIN messages m ON m.chat_id=cm.chat_id and m.id > cm.last_read_id and m.sender_id <> cm.user_id
+                    and m.parent_id IS NULL and m.deleted_at IS NULL
+                    and NOT EXISTS(SELECT 1 FROM user_blocks b WHERE b.blocker_id=cm.user_id and b.blocked_id=m.sender_id)
+                WHERE cm.chat_id=c.id and cm.user_id=$2
+            ) r ON true
+            WHERE (c.ws_id=$1 OR c.id IN (SELECT chat_id FROM shared_channels WHERE ws_id=$1 and status='active'))
+                and (c.archived_at IS NOT NULL)=$3 order by c.created_at desc
             "#,
         )
         .bind(ws_id as i64)
+        .bind(user_id as i64)
+        .bind(input.archived)
         .fetch_all(pool)
         .await?;
-        Ok(chats)
+        Ok(chats
+            .into_iter()
+            .map(|item| ChatListItem {
+                chat: item.chat.compact(),
+                ..item
+            })
+            .collect())
     }
 
-    pub async fn get_chat_by_id(&self, id: i64) -> Result<Option<Chat>, AppError> {
+    //chats of other workspaces are invisible unless shared with this one
+    pub async fn get_chat_by_id(&self, id: i64, ws_id: u64) -> Result<Option<Chat>, AppError> {
         let chat = sqlx::query_as(
             r#"
-            SELECT id,ws_id,name,type,members,created_at
+            SELECT id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
             FROM chats
-            WHERE id=$1
+            WHERE id=$1 and chat_in_workspace(id,ws_id,$2)
             "#,
         )
         .bind(id)
+        .bind(ws_id as i64)
         .fetch_optional(&self.pool)
         .await?;
         Ok(chat)
     }
-    pub async fn update_chat(&self, input: CreateChat, id: u64) -> Result<Chat, AppError> {
-        let chat_type = self.verify_chat_type(&input).await?;
+    pub async fn update_chat(
+        &self,
+        input: CreateChat,
+        id: u64,
+        ws_id: u64,
+        user_id: u64,
+    ) -> Result<Chat, AppError> {
+        let chat = self.verify_chat_writable(id as _, ws_id).await?;
+        let chat_type = self.verify_chat_type(&input, ws_id, Some(&chat)).await?;
+        let added: Vec<i64> = input
+            .members
+            .iter()
+            .filter(|id| !chat.members.contains(id))
+            .copied()
+            .collect();
+        self.verify_not_blocked(user_id as _, &added, false).await?;
 
         let chat = sqlx::query_as(
             r#"
             UPDATE chats
             SET name=$1,type=$2,members=$3
-            WHERE id=$4
-            RETURNING id,ws_id,name,type,members,created_at
+            WHERE id=$4 and chat_in_workspace(id,ws_id,$5) and archived_at IS NULL
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
             "#,
         )
         .bind(input.name)
         .bind(chat_type)
         .bind(input.members)
         .bind(id as i64)
+        .bind(ws_id as i64)
         .fetch_one(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => AppError::CreateChatError(
+                "direct message with the same members already exists".to_string(),
+            ),
+            e => e.into(),
+        })?;
         Ok(chat)
     }
-    pub async fn delete_chat(&self, id: u64) -> Result<Chat, AppError> {
+
+    //archiving makes the chat read-only for every member, only moderators can do or undo it
+    pub async fn archive_chat(&self, id: u64, user: &User) -> Result<Chat, AppError> {
+        self.verify_chat_moderator(id, user.id as _).await?;
         let chat = sqlx::query_as(
             r#"
-            DELETE FROM chats
-            WHERE id=$1
-            RETURNING id,ws_id,name,type,members,created_at
+            UPDATE chats
+            SET archived_at=now()
+            WHERE id=$1 and chat_in_workspace(id,ws_id,$2) and archived_at IS NULL
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
             "#,
         )
         .bind(id as i64)
-        .fetch_one(&self.pool)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
         .await?;
+        chat.ok_or_else(|| AppError::ChatArchived(id.to_string()))
+    }
+
+    pub async fn unarchive_chat(&self, id: u64, user: &User) -> Result<Chat, AppError> {
+        self.verify_chat_moderator(id, user.id as _).await?;
+        let chat = sqlx::query_as(
+            r#"
+            UPDATE chats
+            SET archived_at=NULL
+            WHERE id=$1 and chat_in_workspace(id,ws_id,$2) and archived_at IS NOT NULL
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            "#,
+        )
+        .bind(id as i64)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        chat.ok_or_else(|| AppError::NotFound(format!("archived chat {}", id)))
+    }
+
+    //permanent deletion is limited to workspace admins, messages and files are purged in background
+    pub async fn delete_chat(&self, id: u64, user: &User) -> Result<Chat, AppError> {
+        if !self
+            .is_workspace_admin(user.ws_id as _, user.id as _)
+            .await?
+        {
+            return Err(AppError::PermissionDenied(
+                "only workspace admin can delete chat".to_string(),
+            ));
+        }
+        let chat = match self.get_chat_by_id(id as _, user.ws_id as _).await? {
+            Some(chat) if chat.ws_id == user.ws_id => chat,
+            Some(_) => {
+                return Err(AppError::PermissionDenied(
+                    "only the host workspace can delete a shared chat".to_string(),
+                ))
+            }
+            None => return Err(AppError::NotFound(id.to_string())),
+        };
+
+        let state = self.clone();
+        tokio::spawn(async move {
+            match state.purge_chat(id as _).await {
+                Ok(_) => info!("chat {} purged", id),
+                Err(e) => warn!("purge chat {} failed: {:?}", id, e),
+            }
+        });
         Ok(chat)
     }
 
-    pub async fn verify_chat_type(&self, input: &CreateChat) -> Result<ChatType, AppError> {
+    pub(crate) async fn purge_chat(&self, id: i64) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        let files: Vec<String> = sqlx::query_scalar(
+            r#"
+            WITH deleted AS (
+                DELETE FROM messages
+                WHERE chat_id=$1
+                RETURNING files
+            )
+            SELECT DISTINCT unnest(files) FROM deleted
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query(r#"DELETE FROM chats WHERE id=$1"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.remove_unreferenced_files(&files).await
+    }
+
+    //archived chats are read-only
+    pub async fn verify_chat_writable(&self, id: i64, ws_id: u64) -> Result<Chat, AppError> {
+        match self.get_chat_by_id(id, ws_id).await? {
+            Some(chat) if chat.archived_at.is_some() => Err(AppError::ChatArchived(id.to_string())),
+            Some(chat) => Ok(chat),
+            None => Err(AppError::NotFound(id.to_string())),
+        }
+    }
+
+    pub async fn verify_chat_type(
+        &self,
+        input: &CreateChat,
+        ws_id: u64,
+        current: Option<&Chat>,
+    ) -> Result<ChatType, AppError> {
         //对话成员必须大于2人
         let len = input.members.len();
         if len < 2 {
@@ -108,11 +334,30 @@ impl AppState {
             ));
         }
 
-        //verify if all members exist
-        let users = self.fetch_chat_user_by_ids(&input.members).await?;
-        if users.len() != len {
+        //verify if all members exist in the workspace, members from the other workspaces
+        //of a shared chat are managed by their own workspace and must stay unchanged
+        let users = self.fetch_chat_user_by_ids(&input.members, ws_id).await?;
+        let mut foreign: Vec<i64> = input
+            .members
+            .iter()
+            .filter(|id| !users.iter().any(|u| u.id == **id))
+            .copied()
+            .collect();
+        let mut current_foreign: Vec<i64> = match current {
+            Some(chat) => {
+                sqlx::query_scalar(r#"SELECT id FROM users WHERE id=ANY($1) and ws_id<>$2"#)
+                    .bind(&chat.members)
+                    .bind(ws_id as i64)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+            None => vec![],
+        };
+        foreign.sort_unstable();
+        current_foreign.sort_unstable();
+        if users.len() + foreign.len() != len || foreign != current_foreign {
             return Err(AppError::CreateChatError(
-                "Some members do not exist".to_string(),
+                "Some members do not exist in this workspace".to_string(),
             ));
         }
 
@@ -130,19 +375,29 @@ impl AppState {
         Ok(chat_type)
     }
 
-    pub async fn is_chat_member(&self, chat_id: i64, user_id: i64) -> Result<bool, AppError> {
-        let chat = sqlx::query(
+    //chat of the member, only when the chat belongs to the workspace of the member
+    pub async fn find_member_chat(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+    ) -> Result<Option<Chat>, AppError> {
+        let chat = sqlx::query_as(
             r#"
-            SELECT id
-            FROM chats
-            WHERE id=$1 and $2=ANY(members)
+            SELECT c.id,c.ws_id,c.name,c.type,c.members,c.created_at,c.archived_at,c.description,c.topic,c.icon,c.member_count
+            FROM chats c
+            JOIN users u ON u.id=$2 and chat_in_workspace(c.id,c.ws_id,u.ws_id)
+            WHERE c.id=$1 and $2=ANY(c.members)
             "#,
         )
         .bind(chat_id)
         .bind(user_id)
         .fetch_optional(&self.pool)
         .await?;
-        Ok(chat.is_some())
+        Ok(chat)
+    }
+
+    pub async fn is_chat_member(&self, chat_id: i64, user_id: i64) -> Result<bool, AppError> {
+        Ok(self.find_member_chat(chat_id, user_id).await?.is_some())
     }
 }
 
@@ -168,6 +423,7 @@ mod tests {
     use anyhow::Ok;
 
     use super::*;
+    use crate::models::{ChatFile, CreateMessage, UpdateChatIcon};
 
     #[tokio::test]
     async fn test_create_chat() -> Result<()> {
@@ -175,7 +431,7 @@ mod tests {
         let input = CreateChat::new("", &[1, 2], false);
         let ws_id = 1;
         let chat = state
-            .create_chat(input, ws_id)
+            .create_chat(input, ws_id, 1)
             .await
             .expect("create chat failed");
         assert_eq!(chat.members.len(), 2);
@@ -191,7 +447,7 @@ mod tests {
         let ws_id = 1;
         let members = &[1, 2, 3];
         let input = CreateChat::new("", members, false);
-        let chat = state.create_chat(input, ws_id).await.unwrap();
+        let chat = state.create_chat(input, ws_id, 1).await.unwrap();
         assert_eq!(chat.members.len(), 3);
         assert_eq!(chat.ws_id, ws_id as i64);
         assert_eq!(chat.r#type, ChatType::Group);
@@ -205,10 +461,152 @@ mod tests {
         let ws_id = 1;
         let members = &[1, 2, 3];
         let input = CreateChat::new("public chat", members, true);
-        let chat = state.create_chat(input, ws_id).await.unwrap();
+        let chat = state.create_chat(input, ws_id, 1).await.unwrap();
         assert_eq!(chat.members.len(), 3);
         assert_eq!(chat.ws_id, ws_id as i64);
         assert_eq!(chat.r#type, ChatType::PublicChannel);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_get_or_create_dm_should_be_idempotent() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let user = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+
+        let input = CreateDirectMessage { members: vec![3] };
+        let (chat, created) = state.get_or_create_dm(input.clone(), &user).await?;
+        assert!(created);
+        assert_eq!(chat.r#type, ChatType::Single);
+        assert_eq!(chat.members, vec![1, 3]);
+        let (chat2, created) = state.get_or_create_dm(input, &user).await?;
+        assert!(!created);
+        assert_eq!(chat.id, chat2.id);
+
+        let user3 = state
+            .find_user_by_email("kevin3.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let input = CreateDirectMessage {
+            members: vec![1, 3],
+        };
+        let (chat3, created) = state.get_or_create_dm(input, &user3).await?;
+        assert!(!created);
+        assert_eq!(chat.id, chat3.id);
+
+        //group dm dedupe on the exact member set
+        let input = CreateDirectMessage {
+            members: vec![3, 2],
+        };
+        let (group, created) = state.get_or_create_dm(input, &user).await?;
+        assert!(!created);
+        assert_eq!(group.r#type, ChatType::Group);
+        let chat = state
+            .create_chat(CreateChat::new("", &[2, 1, 3], false), 1, 1)
+            .await?;
+        assert_eq!(chat.id, group.id);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_archived_chat_should_be_read_only() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+
+        //only moderators and the workspace admin can archive
+        let ret = state.archive_chat(2, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let chat = state.archive_chat(2, &kevin).await?;
+        assert!(chat.archived_at.is_some());
+        assert!(state.archive_chat(2, &kevin).await.is_err());
+
+        let input = CreateMessage {
+            content: "hello".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        let ret = state.create_message(input.clone(), 2, 1).await;
+        assert!(matches!(ret, Err(AppError::ChatArchived(_))));
+
+        let chats = state.fetch_all_chat(1, 1, ListChats::default()).await?;
+        assert!(chats.iter().all(|c| c.chat.id != 2));
+        let chats = state
+            .fetch_all_chat(1, 1, ListChats { archived: true })
+            .await?;
+        assert_eq!(chats.len(), 1);
+
+        let ret = state.unarchive_chat(2, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let chat = state.unarchive_chat(2, &kevin).await?;
+        assert!(chat.archived_at.is_none());
+        state.create_message(input, 2, 1).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_chat_should_purge_messages() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        //the posted file is also the icon of chat 3, it must outlive the purge
+        let file = ChatFile::new(1, "logo.png", b"logo");
+        let path = file.path(&state.config.server.base_dir);
+        tokio::fs::create_dir_all(path.parent().expect("file should have a parent")).await?;
+        tokio::fs::write(&path, b"logo").await?;
+        let input = UpdateChatIcon {
+            icon: Some(file.url()),
+        };
+        state.update_chat_icon(input, 3, 1).await?;
+        let input = CreateMessage {
+            content: "hello".to_string(),
+            files: vec![file.url()],
+            ..Default::default()
+        };
+        state.create_message(input, 2, 1).await?;
+
+        let user = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?;
+        let ret = state.delete_chat(2, &user.unwrap()).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+
+        state.purge_chat(2).await?;
+        assert!(state.get_chat_by_id(2, 1).await?.is_none());
+        let count: i64 = sqlx::query_scalar("SELECT count(*) FROM messages WHERE chat_id=2")
+            .fetch_one(&state.pool)
+            .await?;
+        assert_eq!(count, 0);
+        assert!(path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chat_should_stay_in_workspace() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        //user 4 is in workspace2
+        let input = CreateChat::new("mixed", &[1, 4], false);
+        let ret = state.create_chat(input, 1, 1).await;
+        assert!(matches!(ret, Err(AppError::CreateChatError(_))));
+        assert!(state.get_chat_by_id(6, 1).await?.is_none());
+        assert!(!state.is_chat_member(6, 1).await?);
+
+        //database rejects cross workspace members even without the checks above
+        let ret = sqlx::query(r#"UPDATE chats SET members='{1,2,3,4}' WHERE id=2"#)
+            .execute(&state.pool)
+            .await;
+        assert!(ret.is_err());
+        let ret =
+            sqlx::query(r#"INSERT INTO messages(chat_id,sender_id,content) VALUES(6,1,'hello')"#)
+                .execute(&state.pool)
+                .await;
+        assert!(ret.is_err());
+        Ok(())
+    }
 }
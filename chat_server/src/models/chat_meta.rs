@@ -0,0 +1,222 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use core_lib::{Chat, User};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+use super::ChatFile;
+
+const MAX_TOPIC_LEN: usize = 256;
+const MAX_DESCRIPTION_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateChatTopic {
+    pub topic: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateChatDescription {
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateChatIcon {
+    //url of an uploaded file, e.g. /files/1/0fd/a3e/ed0040e14b47bec49a71f08097b325950d.jpg
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct ChatTopic {
+    pub id: i64,
+    pub chat_id: i64,
+    pub topic: Option<String>,
+    pub changed_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AppState {
+    pub async fn update_chat_topic(
+        &self,
+        input: UpdateChatTopic,
+        id: u64,
+        user: &User,
+    ) -> Result<Chat, AppError> {
+        self.verify_chat_writable(id as _, user.ws_id as _).await?;
+        let topic = normalize(input.topic, MAX_TOPIC_LEN, "topic")?;
+
+        let mut tx = self.pool.begin().await?;
+        let chat = sqlx::query_as(
+            r#"
+            UPDATE chats
+            SET topic=$1
+            WHERE id=$2
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            "#,
+        )
+        .bind(&topic)
+        .bind(id as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO chat_topics(chat_id,topic,changed_by)
+            VALUES($1,$2,$3)
+            "#,
+        )
+        .bind(id as i64)
+        .bind(&topic)
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(chat)
+    }
+
+    pub async fn list_chat_topics(&self, id: u64, ws_id: u64) -> Result<Vec<ChatTopic>, AppError> {
+        let topics = sqlx::query_as(
+            r#"
+            SELECT t.id,t.chat_id,t.topic,t.changed_by,t.created_at
+            FROM chat_topics t
+            JOIN chats c ON c.id=t.chat_id
+            WHERE t.chat_id=$1 and chat_in_workspace(c.id,c.ws_id,$2)
+            ORDER BY t.created_at DESC, t.id DESC
+            "#,
+        )
+        .bind(id as i64)
+        .bind(ws_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(topics)
+    }
+
+    pub async fn update_chat_description(
+        &self,
+        input: UpdateChatDescription,
+        id: u64,
+        ws_id: u64,
+    ) -> Result<Chat, AppError> {
+        self.verify_chat_writable(id as _, ws_id).await?;
+        let description = normalize(input.description, MAX_DESCRIPTION_LEN, "description")?;
+
+        let chat = sqlx::query_as(
+            r#"
+            UPDATE chats
+            SET description=$1
+            WHERE id=$2
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            "#,
+        )
+        .bind(description)
+        .bind(id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(chat)
+    }
+
+    pub async fn update_chat_icon(
+        &self,
+        input: UpdateChatIcon,
+        id: u64,
+        ws_id: u64,
+    ) -> Result<Chat, AppError> {
+        let chat = self.verify_chat_writable(id as _, ws_id).await?;
+        if let Some(icon) = &input.icon {
+            let file = ChatFile::from_str(icon)?;
+            if file.ws_id != chat.ws_id || !file.path(&self.config.server.base_dir).exists() {
+                return Err(AppError::UpdateChatError(
+                    "icon file not exists".to_string(),
+                ));
+            }
+        }
+
+        let chat = sqlx::query_as(
+            r#"
+            UPDATE chats
+            SET icon=$1
+            WHERE id=$2
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            "#,
+        )
+        .bind(input.icon)
+        .bind(id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(chat)
+    }
+}
+
+//trim the value, empty value clears the field
+fn normalize(
+    value: Option<String>,
+    max_len: usize,
+    field: &str,
+) -> Result<Option<String>, AppError> {
+    let value = value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    match value {
+        Some(v) if v.chars().count() > max_len => Err(AppError::UpdateChatError(format!(
+            "{} must be at most {} characters",
+            field, max_len
+        ))),
+        v => Ok(v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_update_chat_topic_should_keep_history() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let input = UpdateChatTopic {
+            topic: Some("release 1.0".to_string()),
+        };
+        let user1 = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let user2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let chat = state.update_chat_topic(input, 2, &user1).await?;
+        assert_eq!(chat.topic.as_deref(), Some("release 1.0"));
+
+        let input = UpdateChatTopic {
+            topic: Some("  ".to_string()),
+        };
+        let chat = state.update_chat_topic(input, 2, &user2).await?;
+        assert!(chat.topic.is_none());
+
+        let topics = state.list_chat_topics(2, 1).await?;
+        assert!(state.list_chat_topics(2, 2).await?.is_empty());
+        assert_eq!(topics.len(), 2);
+        assert_eq!(topics[0].changed_by, 2);
+        assert_eq!(topics[1].topic.as_deref(), Some("release 1.0"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_chat_description_should_check_length() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let input = UpdateChatDescription {
+            description: Some("a".repeat(MAX_DESCRIPTION_LEN + 1)),
+        };
+        let ret = state.update_chat_description(input, 2, 1).await;
+        assert!(matches!(ret, Err(AppError::UpdateChatError(_))));
+
+        let input = UpdateChatDescription {
+            description: Some("general discussion".to_string()),
+        };
+        let chat = state.update_chat_description(input, 2, 1).await?;
+        assert_eq!(chat.description.as_deref(), Some("general discussion"));
+        Ok(())
+    }
+}
@@ -3,12 +3,14 @@ use std::{
     str::FromStr,
 };
 
-use crate::AppError;
+use crate::{AppError, AppState};
+use core_lib::User;
 
 use super::ChatFile;
 
 use sha1::{Digest, Sha1};
-use tracing::info;
+use tokio::fs;
+use tracing::{info, warn};
 
 impl ChatFile {
     pub fn new(ws_id: i64, filename: &str, data: &[u8]) -> Self {
@@ -36,6 +38,69 @@ impl ChatFile {
     }
 }
 
+impl AppState {
+    //posted files are readable when the message is visible to the user in one of
+    //their chats, files not posted yet stay readable inside their workspace
+    pub async fn can_access_file(&self, url: &str, user: &User) -> Result<bool, AppError> {
+        let same_workspace = ChatFile::from_str(url)?.ws_id == user.ws_id;
+        let ret = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1
+                FROM chat_members cm
+                JOIN messages m ON m.chat_id=cm.chat_id
+                WHERE cm.user_id=$2 and m.files @> ARRAY[$1]
+                    and m.created_at >= history_start(cm.chat_id,cm.user_id)
+            ) OR EXISTS(
+                SELECT 1
+                FROM chat_members cm
+                JOIN chats c ON c.id=cm.chat_id
+                WHERE cm.user_id=$2 and c.icon=$1
+            ) OR ($3 and NOT EXISTS(SELECT 1 FROM messages WHERE files @> ARRAY[$1]))
+            "#,
+        )
+        .bind(url)
+        .bind(user.id)
+        .bind(same_workspace)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(ret)
+    }
+
+    //remove files from disk when no message, chat icon or workspace emoji
+    //references them any more
+    pub async fn remove_unreferenced_files(&self, files: &[String]) -> Result<(), AppError> {
+        let base_dir = &self.config.server.base_dir;
+        for url in files {
+            let referenced: bool = sqlx::query_scalar(
+                r#"
+                SELECT EXISTS(SELECT 1 FROM messages WHERE files @> ARRAY[$1])
+                    OR EXISTS(SELECT 1 FROM chats WHERE icon=$1)
+                    OR EXISTS(SELECT 1 FROM workspace_emojis WHERE url=$1)
+                "#,
+            )
+            .bind(url)
+            .fetch_one(&self.pool)
+            .await?;
+            if referenced {
+                continue;
+            }
+            let path = match ChatFile::from_str(url) {
+                Ok(file) => file.path(base_dir),
+                Err(e) => {
+                    warn!("invalid file url {}: {:?}", url, e);
+                    continue;
+                }
+            };
+            if path.exists() {
+                fs::remove_file(&path).await?;
+                info!("file {:?} removed", path);
+            }
+        }
+        Ok(())
+    }
+}
+
 impl FromStr for ChatFile {
     type Err = AppError;
     //convert /files/1/0fd/a3e/ed0040e14b47bec49a71f08097b325950d.jpg to ChatFile
@@ -0,0 +1,402 @@
+use chrono::{DateTime, Utc};
+use core_lib::{Chat, ChatType, User};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+use super::CreateChat;
+
+const MAX_HANDLE_LEN: usize = 32;
+//handles reserved for chat wide mentions
+const RESERVED_HANDLES: [&str; 3] = ["here", "channel", "everyone"];
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct UserGroup {
+    pub id: i64,
+    pub ws_id: i64,
+    //mentioned as @handle
+    pub handle: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+    pub members: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct CreateUserGroup {
+    pub handle: String,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub members: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateUserGroup {
+    pub handle: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateUserGroupMembers {
+    pub members: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct AddGroupToChat {
+    pub group_id: i64,
+}
+
+impl AppState {
+    //the directory of groups in the workspace
+    pub async fn list_user_groups(&self, ws_id: u64) -> Result<Vec<UserGroup>, AppError> {
+        let groups = sqlx::query_as(
+            r#"
+            SELECT g.id,g.ws_id,g.handle,g.name,g.description,g.created_by,g.created_at,
+                coalesce(array_agg(gm.user_id ORDER BY gm.user_id) FILTER (WHERE gm.user_id IS NOT NULL),'{}') AS members
+            FROM user_groups g
+            LEFT JOIN user_group_members gm ON gm.group_id=g.id
+            WHERE g.ws_id=$1
+            GROUP BY g.id
+            ORDER BY g.handle
+            "#,
+        )
+        .bind(ws_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(groups)
+    }
+
+    pub async fn get_user_group(&self, id: u64, ws_id: u64) -> Result<UserGroup, AppError> {
+        let group = sqlx::query_as(
+            r#"
+            SELECT g.id,g.ws_id,g.handle,g.name,g.description,g.created_by,g.created_at,
+                coalesce(array_agg(gm.user_id ORDER BY gm.user_id) FILTER (WHERE gm.user_id IS NOT NULL),'{}') AS members
+            FROM user_groups g
+            LEFT JOIN user_group_members gm ON gm.group_id=g.id
+            WHERE g.id=$1 and g.ws_id=$2
+            GROUP BY g.id
+            "#,
+        )
+        .bind(id as i64)
+        .bind(ws_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        group.ok_or_else(|| AppError::NotFound(format!("user group {}", id)))
+    }
+
+    pub async fn create_user_group(
+        &self,
+        input: CreateUserGroup,
+        user: &User,
+    ) -> Result<UserGroup, AppError> {
+        verify_group_handle(&input.handle)?;
+        verify_group_name(&input.name)?;
+        let members = self.verify_group_members(input.members, user).await?;
+
+        let mut tx = self.pool.begin().await?;
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO user_groups(ws_id,handle,name,description,created_by)
+            VALUES($1,$2,$3,$4,$5)
+            RETURNING id
+            "#,
+        )
+        .bind(user.ws_id)
+        .bind(&input.handle)
+        .bind(input.name.trim())
+        .bind(input.description)
+        .bind(user.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| map_handle_conflict(e, &input.handle))?;
+        sqlx::query(
+            r#"
+            INSERT INTO user_group_members(group_id,user_id)
+            SELECT $1,unnest($2::BIGINT[])
+            "#,
+        )
+        .bind(id)
+        .bind(&members)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.get_user_group(id as _, user.ws_id as _).await
+    }
+
+    pub async fn update_user_group(
+        &self,
+        input: UpdateUserGroup,
+        id: u64,
+        user: &User,
+    ) -> Result<UserGroup, AppError> {
+        self.verify_group_manager(id, user).await?;
+        if let Some(handle) = &input.handle {
+            verify_group_handle(handle)?;
+        }
+        if let Some(name) = &input.name {
+            verify_group_name(name)?;
+        }
+        let handle = input.handle.clone().unwrap_or_default();
+        sqlx::query(
+            r#"
+            UPDATE user_groups
+            SET handle=coalesce($1,handle),name=coalesce($2,name),description=coalesce($3,description)
+            WHERE id=$4
+            "#,
+        )
+        .bind(input.handle)
+        .bind(input.name.map(|n| n.trim().to_string()))
+        .bind(input.description)
+        .bind(id as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| map_handle_conflict(e, &handle))?;
+        self.get_user_group(id, user.ws_id as _).await
+    }
+
+    pub async fn delete_user_group(&self, id: u64, user: &User) -> Result<(), AppError> {
+        self.verify_group_manager(id, user).await?;
+        sqlx::query(r#"DELETE FROM user_groups WHERE id=$1"#)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    //replace the members of the group
+    pub async fn update_user_group_members(
+        &self,
+        input: UpdateUserGroupMembers,
+        id: u64,
+        user: &User,
+    ) -> Result<UserGroup, AppError> {
+        self.verify_group_manager(id, user).await?;
+        let members = self.verify_group_members(input.members, user).await?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"DELETE FROM user_group_members WHERE group_id=$1 and NOT (user_id=ANY($2))"#,
+        )
+        .bind(id as i64)
+        .bind(&members)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO user_group_members(group_id,user_id)
+            SELECT $1,unnest($2::BIGINT[])
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(id as i64)
+        .bind(&members)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        self.get_user_group(id, user.ws_id as _).await
+    }
+
+    //add every member of the group to a chat of the same workspace
+    pub async fn add_group_to_chat(
+        &self,
+        input: AddGroupToChat,
+        chat_id: u64,
+        user: &User,
+    ) -> Result<Chat, AppError> {
+        let chat = self
+            .verify_chat_writable(chat_id as _, user.ws_id as _)
+            .await?;
+        if chat.r#type == ChatType::Single {
+            return Err(AppError::UserGroupError(
+                "groups can't be added to a direct message".to_string(),
+            ));
+        }
+        if chat.ws_id != user.ws_id {
+            return Err(AppError::PermissionDenied(
+                "groups can only be added to chats of their workspace".to_string(),
+            ));
+        }
+        let group = self
+            .get_user_group(input.group_id as _, user.ws_id as _)
+            .await?;
+        let mut members = chat.members.clone();
+        members.extend(group.members.iter().filter(|id| !chat.members.contains(id)));
+        if members.len() == chat.members.len() {
+            return Ok(chat);
+        }
+        let input = CreateChat {
+            name: chat.name.clone(),
+            members,
+            public: chat.r#type == ChatType::PublicChannel,
+        };
+        self.update_chat(input, chat_id, user.ws_id as _, user.id as _)
+            .await
+    }
+
+    //the creator of a group and the workspace admin manage it
+    async fn verify_group_manager(&self, id: u64, user: &User) -> Result<(), AppError> {
+        let group = self.get_user_group(id, user.ws_id as _).await?;
+        if group.created_by != user.id
+            && !self
+                .is_workspace_admin(user.ws_id as _, user.id as _)
+                .await?
+        {
+            return Err(AppError::PermissionDenied(
+                "only the creator of the group or workspace admin can change it".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn verify_group_members(
+        &self,
+        mut members: Vec<i64>,
+        user: &User,
+    ) -> Result<Vec<i64>, AppError> {
+        members.sort_unstable();
+        members.dedup();
+        let users = self
+            .fetch_chat_user_by_ids(&members, user.ws_id as _)
+            .await?;
+        if users.len() != members.len() {
+            return Err(AppError::UserGroupError(
+                "Some members do not exist in this workspace".to_string(),
+            ));
+        }
+        Ok(members)
+    }
+}
+
+//lowercase letters, digits, '-' and '_'
+fn verify_group_handle(handle: &str) -> Result<(), AppError> {
+    let valid = (2..=MAX_HANDLE_LEN).contains(&handle.len())
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::UserGroupError(format!(
+            "handle must be 2 to {} lowercase letters, digits, '-' or '_'",
+            MAX_HANDLE_LEN
+        )));
+    }
+    if RESERVED_HANDLES.contains(&handle) {
+        return Err(AppError::UserGroupError(format!(
+            "handle {} is reserved",
+            handle
+        )));
+    }
+    Ok(())
+}
+
+fn verify_group_name(name: &str) -> Result<(), AppError> {
+    let len = name.trim().chars().count();
+    if len == 0 || len > 64 {
+        return Err(AppError::UserGroupError(
+            "name must be 1 to 64 characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn map_handle_conflict(e: sqlx::Error, handle: &str) -> AppError {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            AppError::UserGroupError(format!("handle {} already exists", handle))
+        }
+        e => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateMessage, ListChats};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_user_group_should_be_mentioned_and_added_to_chat() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+
+        let input = CreateUserGroup {
+            handle: "Backend".to_string(),
+            name: "Backend".to_string(),
+            ..Default::default()
+        };
+        let ret = state.create_user_group(input, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::UserGroupError(_))));
+        let input = CreateUserGroup {
+            handle: "backend".to_string(),
+            name: "Backend".to_string(),
+            description: None,
+            members: vec![3, 2, 4],
+        };
+        let ret = state.create_user_group(input.clone(), &kevin2).await;
+        assert!(matches!(ret, Err(AppError::UserGroupError(_))));
+        let input = CreateUserGroup {
+            members: vec![3, 2, 3],
+            ..input
+        };
+        let group = state.create_user_group(input.clone(), &kevin2).await?;
+        assert_eq!(group.members, vec![2, 3]);
+        let ret = state.create_user_group(input, &kevin).await;
+        assert!(matches!(ret, Err(AppError::UserGroupError(_))));
+        assert_eq!(state.list_user_groups(1).await?.len(), 1);
+        assert!(state.list_user_groups(2).await?.is_empty());
+
+        //@backend mentions kevin3 in general
+        let input = CreateMessage {
+            content: "ping @backend".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        state.create_message(input, 2, 1).await?;
+        let chats = state.fetch_all_chat(1, 3, ListChats::default()).await?;
+        let general = chats.iter().find(|c| c.chat.id == 2).unwrap();
+        assert_eq!(general.mention_count, 1);
+
+        //only the creator and the admin manage the group
+        let kevin3 = state
+            .find_user_by_email("kevin3.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let input = UpdateUserGroupMembers { members: vec![2] };
+        let ret = state
+            .update_user_group_members(input.clone(), group.id as _, &kevin3)
+            .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let group = state
+            .update_user_group_members(input, group.id as _, &kevin)
+            .await?;
+        assert_eq!(group.members, vec![2]);
+
+        //chat 3 is private with kevin and kevin2
+        let input = UpdateUserGroupMembers {
+            members: vec![2, 3],
+        };
+        state
+            .update_user_group_members(input, group.id as _, &kevin2)
+            .await?;
+        let input = AddGroupToChat { group_id: group.id };
+        let chat = state.add_group_to_chat(input.clone(), 3, &kevin).await?;
+        assert_eq!(chat.members, vec![1, 2, 3]);
+        let ret = state.add_group_to_chat(input, 4, &kevin).await;
+        assert!(matches!(ret, Err(AppError::UserGroupError(_))));
+
+        state.delete_user_group(group.id as _, &kevin2).await?;
+        assert!(state.list_user_groups(1).await?.is_empty());
+        Ok(())
+    }
+}
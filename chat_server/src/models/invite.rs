@@ -0,0 +1,345 @@
+use chrono::{DateTime, Duration, Utc};
+use core_lib::{Chat, ChatType, User};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+use super::audit::{write_audit_log, AuditAction};
+
+const DEFAULT_INVITE_TTL_SECS: i64 = 7 * 24 * 3600;
+const MAX_INVITE_TTL_SECS: i64 = 30 * 24 * 3600;
+//unnamed groups are limited to 8 members
+const MAX_GROUP_MEMBERS: usize = 8;
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct CreateChatInvite {
+    //seconds until the link expires, 7 days by default and 30 days at most
+    pub expires_in: Option<i64>,
+    //unlimited if absent
+    pub max_uses: Option<i32>,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct ChatInvite {
+    pub id: i64,
+    pub chat_id: i64,
+    pub token: String,
+    pub created_by: i64,
+    pub expires_at: DateTime<Utc>,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ChatInvite {
+    fn is_usable(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none()
+            && self.expires_at > now
+            && self.max_uses.is_none_or(|max| self.uses < max)
+    }
+}
+
+impl AppState {
+    //invite links are for private channels and groups, created by chat moderators
+    pub async fn create_chat_invite(
+        &self,
+        input: CreateChatInvite,
+        chat_id: u64,
+        user: &User,
+    ) -> Result<ChatInvite, AppError> {
+        self.verify_chat_moderator(chat_id, user.id as _).await?;
+        let chat = self
+            .verify_chat_writable(chat_id as _, user.ws_id as _)
+            .await?;
+        if !matches!(chat.r#type, ChatType::PrivateChannel | ChatType::Group) {
+            return Err(AppError::InviteError(
+                "invite links are only for private channels and groups".to_string(),
+            ));
+        }
+        let expires_in = input.expires_in.unwrap_or(DEFAULT_INVITE_TTL_SECS);
+        if !(1..=MAX_INVITE_TTL_SECS).contains(&expires_in) {
+            return Err(AppError::InviteError(format!(
+                "expires_in must be between 1 and {} seconds",
+                MAX_INVITE_TTL_SECS
+            )));
+        }
+        if input.max_uses.is_some_and(|max| max < 1) {
+            return Err(AppError::InviteError(
+                "max_uses must be positive".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let invite: ChatInvite = sqlx::query_as(
+            r#"
+            INSERT INTO chat_invites(chat_id,created_by,expires_at,max_uses)
+            VALUES($1,$2,$3,$4)
+            RETURNING id,chat_id,token,created_by,expires_at,max_uses,uses,revoked_at,created_at
+            "#,
+        )
+        .bind(chat.id)
+        .bind(user.id)
+        .bind(Utc::now() + Duration::seconds(expires_in))
+        .bind(input.max_uses)
+        .fetch_one(&mut *tx)
+        .await?;
+        write_audit_log(
+            &mut tx,
+            chat.id,
+            user.id,
+            AuditAction::InviteCreated,
+            json!({"invite_id": invite.id, "expires_at": invite.expires_at, "max_uses": invite.max_uses}),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(invite)
+    }
+
+    pub async fn list_chat_invites(
+        &self,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<Vec<ChatInvite>, AppError> {
+        self.verify_chat_moderator(chat_id, user_id).await?;
+        let invites = sqlx::query_as(
+            r#"
+            SELECT id,chat_id,token,created_by,expires_at,max_uses,uses,revoked_at,created_at
+            FROM chat_invites
+            WHERE chat_id=$1
+            ORDER BY created_at DESC, id DESC
+            "#,
+        )
+        .bind(chat_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(invites)
+    }
+
+    pub async fn revoke_chat_invite(&self, id: u64, user: &User) -> Result<ChatInvite, AppError> {
+        let chat_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT i.chat_id
+            FROM chat_invites i
+            JOIN chats c ON c.id=i.chat_id
+            WHERE i.id=$1 and chat_in_workspace(c.id,c.ws_id,$2)
+            "#,
+        )
+        .bind(id as i64)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(chat_id) = chat_id else {
+            return Err(AppError::NotFound(format!("invite {}", id)));
+        };
+        self.verify_chat_moderator(chat_id as _, user.id as _)
+            .await?;
+
+        let mut tx = self.pool.begin().await?;
+        let invite: ChatInvite = sqlx::query_as(
+            r#"
+            UPDATE chat_invites
+            SET revoked_at=coalesce(revoked_at,now())
+            WHERE id=$1
+            RETURNING id,chat_id,token,created_by,expires_at,max_uses,uses,revoked_at,created_at
+            "#,
+        )
+        .bind(id as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+        write_audit_log(
+            &mut tx,
+            chat_id,
+            user.id,
+            AuditAction::InviteRevoked,
+            json!({"invite_id": id}),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(invite)
+    }
+
+    //only users of the workspace of the chat can join through a link
+    pub async fn redeem_chat_invite(&self, token: &str, user: &User) -> Result<Chat, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let invite: Option<ChatInvite> = sqlx::query_as(
+            r#"
+            SELECT id,chat_id,token,created_by,expires_at,max_uses,uses,revoked_at,created_at
+            FROM chat_invites
+            WHERE token=$1
+            FOR UPDATE
+            "#,
+        )
+        .bind(token)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let invite = match invite {
+            Some(invite) if invite.is_usable(Utc::now()) => invite,
+            Some(_) => {
+                return Err(AppError::InviteExpired(
+                    "invite link is expired, revoked or used up".to_string(),
+                ))
+            }
+            None => return Err(AppError::NotFound("invite link".to_string())),
+        };
+        let chat: Chat = sqlx::query_as(
+            r#"
+            SELECT id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            FROM chats
+            WHERE id=$1
+            FOR UPDATE
+            "#,
+        )
+        .bind(invite.chat_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if chat.ws_id != user.ws_id {
+            return Err(AppError::PermissionDenied(
+                "invite link belongs to another workspace".to_string(),
+            ));
+        }
+        if chat.archived_at.is_some() {
+            return Err(AppError::ChatArchived(chat.id.to_string()));
+        }
+        if chat.members.contains(&user.id) {
+            return Ok(chat);
+        }
+        if chat.r#type == ChatType::Group
+            && chat.name.is_none()
+            && chat.members.len() >= MAX_GROUP_MEMBERS
+        {
+            return Err(AppError::InviteError(
+                "Group chat with more than 8 members must have a name".to_string(),
+            ));
+        }
+
+        //an unnamed group is keyed by its members, joining can make it a duplicate
+        let chat = sqlx::query_as(
+            r#"
+            UPDATE chats
+            SET members=array_append(members,$2)
+            WHERE id=$1
+            RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+            "#,
+        )
+        .bind(chat.id)
+        .bind(user.id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => AppError::InviteError(
+                "a direct message with the same members already exists".to_string(),
+            ),
+            e => e.into(),
+        })?;
+        sqlx::query(r#"UPDATE chat_invites SET uses=uses+1 WHERE id=$1"#)
+            .bind(invite.id)
+            .execute(&mut *tx)
+            .await?;
+        write_audit_log(
+            &mut tx,
+            invite.chat_id,
+            user.id,
+            AuditAction::InviteRedeemed,
+            json!({"invite_id": invite.id}),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(chat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChatRole, CreateChat, CreateUser, UpdateMemberRole};
+    use anyhow::Result;
+
+    async fn user(state: &AppState, email: &str) -> Result<User> {
+        Ok(state.find_user_by_email(email).await?.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_chat_invite_should_be_limited_and_audited() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = user(&state, "kevin.yang.xgz@gmail.com").await?;
+        let kevin2 = user(&state, "kevin2.yang.xgz@gmail.com").await?;
+        let kevin3 = user(&state, "kevin3.yang.xgz@gmail.com").await?;
+        let alice = user(&state, "alice@acme.org").await?;
+
+        //chat 3 is private with kevin and kevin2, kevin is the workspace admin
+        let input = CreateChatInvite {
+            expires_in: None,
+            max_uses: Some(1),
+        };
+        let ret = state.create_chat_invite(input.clone(), 3, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let input_role = UpdateMemberRole {
+            user_id: 2,
+            role: ChatRole::Moderator,
+        };
+        state.update_member_role(input_role, 3, 1).await?;
+        let invite = state.create_chat_invite(input, 3, &kevin2).await?;
+        assert_eq!(invite.token.len(), 64);
+
+        let ret = state.redeem_chat_invite(&invite.token, &alice).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let chat = state.redeem_chat_invite(&invite.token, &kevin3).await?;
+        assert_eq!(chat.members, vec![1, 2, 3]);
+        //used up
+        let ret = state.redeem_chat_invite(&invite.token, &kevin3).await;
+        assert!(matches!(ret, Err(AppError::InviteExpired(_))));
+
+        let invite = state
+            .create_chat_invite(CreateChatInvite::default(), 3, &kevin)
+            .await?;
+        state.revoke_chat_invite(invite.id as _, &kevin2).await?;
+        let ret = state.redeem_chat_invite(&invite.token, &kevin3).await;
+        assert!(matches!(ret, Err(AppError::InviteExpired(_))));
+
+        let logs = state.list_chat_audit_logs(3).await?;
+        let actions: Vec<_> = logs.iter().map(|l| l.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::InviteRevoked,
+                AuditAction::InviteCreated,
+                AuditAction::InviteRedeemed,
+                AuditAction::InviteCreated,
+                AuditAction::RoleChanged,
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redeem_chat_invite_should_reject_duplicate_group() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = user(&state, "kevin.yang.xgz@gmail.com").await?;
+        let kevin3 = user(&state, "kevin3.yang.xgz@gmail.com").await?;
+        let input = CreateUser {
+            workspace: "workspace1".to_string(),
+            ..CreateUser::new("kevin4", "kevin4.yang.xgz@gmail.com", "password123456")
+        };
+        let kevin4 = state.create_user(&input).await?;
+
+        //kevin3 joining the group of kevin, kevin2 and kevin4 would duplicate this one
+        let members = [kevin.id, 2, kevin3.id, kevin4.id];
+        state
+            .create_chat(CreateChat::new("", &members, false), 1, 1)
+            .await?;
+        let members = [kevin.id, 2, kevin4.id];
+        let group = state
+            .create_chat(CreateChat::new("", &members, false), 1, 1)
+            .await?;
+        let invite = state
+            .create_chat_invite(CreateChatInvite::default(), group.id as _, &kevin)
+            .await?;
+        let ret = state.redeem_chat_invite(&invite.token, &kevin3).await;
+        assert!(matches!(ret, Err(AppError::InviteError(_))));
+        Ok(())
+    }
+}
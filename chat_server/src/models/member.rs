@@ -0,0 +1,409 @@
+use chrono::{DateTime, Utc};
+use core_lib::{ChatUser, NotifyLevel, ReadMarker};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{AppError, AppState};
+
+use super::audit::{write_audit_log, AuditAction};
+
+const DEFAULT_MEMBERS_PAGE_SIZE: u64 = 50;
+const MAX_MEMBERS_PAGE_SIZE: u64 = 200;
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type, ToSchema, Deserialize, Serialize,
+)]
+#[sqlx(type_name = "chat_role", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRole {
+    #[default]
+    Member,
+    //may post in chats restricted to moderators
+    Poster,
+    Moderator,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateNotifyPrefs {
+    #[serde(default)]
+    pub notify_level: NotifyLevel,
+    //no notification until this time, regardless of notify_level
+    pub muted_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct NotifyPrefs {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub notify_level: NotifyLevel,
+    pub muted_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateMemberRole {
+    pub user_id: i64,
+    pub role: ChatRole,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct MemberRole {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub role: ChatRole,
+}
+
+#[derive(Debug, Clone, Default, IntoParams, Deserialize, Serialize)]
+pub struct ListChatMembers {
+    //members with id greater than this one, ordered by id
+    pub after_id: Option<i64>,
+    pub page_size: Option<u64>,
+    //matches fullname or email
+    pub search: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct ChatMember {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub user: ChatUser,
+    pub role: ChatRole,
+    pub joined_at: Option<DateTime<Utc>>,
+    //holding an event stream of notify_server
+    pub online: bool,
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct MarkChatRead {
+    //mark read up to this message, latest message if absent
+    pub message_id: Option<i64>,
+}
+
+impl AppState {
+    pub async fn get_notify_prefs(
+        &self,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<NotifyPrefs, AppError> {
+        let prefs = sqlx::query_as(
+            r#"
+            SELECT chat_id,user_id,notify_level,muted_until
+            FROM chat_members
+            WHERE chat_id=$1 and user_id=$2
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(user_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        prefs.ok_or_else(|| AppError::NotFound(format!("member {} of chat {}", user_id, chat_id)))
+    }
+
+    pub async fn update_notify_prefs(
+        &self,
+        input: UpdateNotifyPrefs,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<NotifyPrefs, AppError> {
+        let prefs = sqlx::query_as(
+            r#"
+            UPDATE chat_members
+            SET notify_level=$1,muted_until=$2
+            WHERE chat_id=$3 and user_id=$4
+            RETURNING chat_id,user_id,notify_level,muted_until
+            "#,
+        )
+        .bind(input.notify_level)
+        .bind(input.muted_until)
+        .bind(chat_id as i64)
+        .bind(user_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        prefs.ok_or_else(|| AppError::NotFound(format!("member {} of chat {}", user_id, chat_id)))
+    }
+
+    pub async fn get_read_marker(
+        &self,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<ReadMarker, AppError> {
+        let marker = sqlx::query_as(
+            r#"
+            SELECT chat_id,user_id,last_read_id
+            FROM chat_members
+            WHERE chat_id=$1 and user_id=$2
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(user_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        marker.ok_or_else(|| AppError::NotFound(format!("member {} of chat {}", user_id, chat_id)))
+    }
+
+    pub async fn list_chat_members(
+        &self,
+        input: ListChatMembers,
+        chat_id: u64,
+    ) -> Result<Vec<ChatMember>, AppError> {
+        let page_size = input
+            .page_size
+            .unwrap_or(DEFAULT_MEMBERS_PAGE_SIZE)
+            .clamp(1, MAX_MEMBERS_PAGE_SIZE);
+        let search = input.search.filter(|s| !s.trim().is_empty()).map(|s| {
+            let s = s.trim().replace('\\', "\\\\");
+            format!("%{}%", s.replace('%', "\\%").replace('_', "\\_"))
+        });
+        let members = sqlx::query_as(
+            r#"
+            SELECT u.id,u.fullname,u.email,cm.role,cm.joined_at,
+                coalesce(p.online,false) AS online,
+                p.last_seen_at
+            FROM chat_members cm
+            JOIN users u ON u.id=cm.user_id
+            LEFT JOIN (
+                SELECT user_id,bool_or(connections > 0) AS online,max(last_seen_at) AS last_seen_at
+                FROM user_presence
+                GROUP BY user_id
+            ) p ON p.user_id=cm.user_id
+            WHERE cm.chat_id=$1 and cm.user_id > $2
+                and ($3::TEXT IS NULL OR u.fullname ILIKE $3 OR u.email ILIKE $3)
+            ORDER BY cm.user_id
+            LIMIT $4
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(input.after_id.unwrap_or(0))
+        .bind(search)
+        .bind(page_size as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    //moderators of the chat and admin of the host workspace
+    pub async fn is_chat_moderator(&self, chat_id: u64, user_id: u64) -> Result<bool, AppError> {
+        let ret = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM chat_members
+                WHERE chat_id=$1 and user_id=$2 and role='moderator'
+            ) OR EXISTS(
+                SELECT 1 FROM chats c
+                JOIN workspaces w ON w.id=c.ws_id
+                WHERE c.id=$1 and w.owner_id=$2
+            )
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(user_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(ret)
+    }
+
+    pub async fn verify_chat_moderator(&self, chat_id: u64, user_id: u64) -> Result<(), AppError> {
+        if !self.is_chat_moderator(chat_id, user_id).await? {
+            return Err(AppError::PermissionDenied(
+                "only chat moderators can do this".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub async fn update_member_role(
+        &self,
+        input: UpdateMemberRole,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<MemberRole, AppError> {
+        self.verify_chat_moderator(chat_id, user_id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        let role: Option<MemberRole> = sqlx::query_as(
+            r#"
+            UPDATE chat_members
+            SET role=$1
+            WHERE chat_id=$2 and user_id=$3
+            RETURNING chat_id,user_id,role
+            "#,
+        )
+        .bind(input.role)
+        .bind(chat_id as i64)
+        .bind(input.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(role) = role else {
+            return Err(AppError::NotFound(format!(
+                "member {} of chat {}",
+                input.user_id, chat_id
+            )));
+        };
+        write_audit_log(
+            &mut tx,
+            chat_id as _,
+            user_id as _,
+            AuditAction::RoleChanged,
+            serde_json::json!({"user_id": input.user_id, "role": input.role}),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(role)
+    }
+
+    //read marker only moves forward
+    pub async fn mark_chat_read(
+        &self,
+        input: MarkChatRead,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<ReadMarker, AppError> {
+        let marker = sqlx::query_as(
+            r#"
+            UPDATE chat_members cm
+            SET last_read_id=GREATEST(cm.last_read_id,m.id)
+            FROM (
+                SELECT max(id) AS id
+                FROM messages
+                WHERE chat_id=$1 and ($3::BIGINT IS NULL OR id=$3)
+            ) m
+            WHERE cm.chat_id=$1 and cm.user_id=$2 and m.id IS NOT NULL
+            RETURNING cm.chat_id,cm.user_id,cm.last_read_id
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(user_id as i64)
+        .bind(input.message_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        match (marker, input.message_id) {
+            (Some(marker), _) => Ok(marker),
+            (None, Some(id)) => Err(AppError::NotFound(format!("message {}", id))),
+            (None, None) => self.get_read_marker(chat_id, user_id).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateChat, CreateMessage, ListChats};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_update_notify_prefs_should_work() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let prefs = state.get_notify_prefs(2, 1).await?;
+        assert_eq!(prefs.notify_level, NotifyLevel::All);
+
+        let muted_until = Utc::now() + chrono::Duration::hours(1);
+        let input = UpdateNotifyPrefs {
+            notify_level: NotifyLevel::Mentions,
+            muted_until: Some(muted_until),
+        };
+        let prefs = state.update_notify_prefs(input, 2, 1).await?;
+        assert_eq!(prefs.notify_level, NotifyLevel::Mentions);
+        assert!(prefs.muted_until.is_some());
+
+        let ret = state.get_notify_prefs(3, 3).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_chat_members_should_page_and_search() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        //kevin2 is connected to one of two instances
+        sqlx::query(
+            "INSERT INTO user_presence(user_id,instance_id,connections) VALUES(2,'a',0),(2,'b',1)",
+        )
+        .execute(&state.pool)
+        .await?;
+        let input = ListChatMembers {
+            page_size: Some(2),
+            ..Default::default()
+        };
+        let members = state.list_chat_members(input, 2).await?;
+        let ids: Vec<_> = members.iter().map(|m| m.user.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert!(!members[0].online && members[1].online);
+
+        let input = ListChatMembers {
+            after_id: Some(2),
+            ..Default::default()
+        };
+        let members = state.list_chat_members(input, 2).await?;
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].user.fullname, "kevin3");
+
+        let input = ListChatMembers {
+            search: Some("KEVIN2".to_string()),
+            ..Default::default()
+        };
+        let members = state.list_chat_members(input, 2).await?;
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].user.id, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chat_members_should_follow_chat_members() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let input = CreateChat::new("team", &[1, 2, 3], false);
+        let chat = state.create_chat(input, 1, 1).await?;
+        assert!(state.get_notify_prefs(chat.id as _, 3).await.is_ok());
+
+        let input = CreateChat::new("team", &[1, 2], false);
+        state.update_chat(input, chat.id as _, 1, 1).await?;
+        assert!(state.get_notify_prefs(chat.id as _, 3).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mark_chat_read_should_update_unread_count() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let mut ids = vec![];
+        for content in ["hello", "hi @kevin", "bye"] {
+            let input = CreateMessage {
+                content: content.to_string(),
+                files: vec![],
+                ..Default::default()
+            };
+            ids.push(state.create_message(input, 2, 2).await?.id);
+        }
+        let unread = |chats: Vec<crate::models::ChatListItem>| {
+            let chat = chats.into_iter().find(|c| c.chat.id == 2).unwrap();
+            (chat.unread_count, chat.mention_count)
+        };
+        let chats = state.fetch_all_chat(1, 1, ListChats::default()).await?;
+        assert_eq!(unread(chats), (3, 1));
+        //sender has nothing unread
+        let chats = state.fetch_all_chat(1, 2, ListChats::default()).await?;
+        assert_eq!(unread(chats), (0, 0));
+
+        let input = MarkChatRead {
+            message_id: Some(ids[1]),
+        };
+        let marker = state.mark_chat_read(input, 2, 1).await?;
+        assert_eq!(marker.last_read_id, ids[1]);
+        let chats = state.fetch_all_chat(1, 1, ListChats::default()).await?;
+        assert_eq!(unread(chats), (1, 0));
+
+        let input = MarkChatRead {
+            message_id: Some(ids[0]),
+        };
+        let marker = state.mark_chat_read(input, 2, 1).await?;
+        assert_eq!(marker.last_read_id, ids[1]);
+
+        let marker = state.mark_chat_read(MarkChatRead::default(), 2, 1).await?;
+        assert_eq!(marker.last_read_id, ids[2]);
+
+        let input = MarkChatRead {
+            message_id: Some(i64::MAX),
+        };
+        let ret = state.mark_chat_read(input, 2, 1).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        Ok(())
+    }
+}
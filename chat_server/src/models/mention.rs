@@ -0,0 +1,211 @@
+use core_lib::{MentionKind, MentionRange};
+use sqlx::{FromRow, PgConnection};
+
+use crate::AppError;
+
+//something that can be mentioned with @name
+#[derive(Debug, Clone, FromRow)]
+struct MentionCandidate {
+    name: String,
+    kind: MentionKind,
+    target_id: Option<i64>,
+    //users outside the chat can't be mentioned
+    member: bool,
+}
+
+//find @user, @handle, @here and @channel tokens in the content of a message sent
+//to the chat, mentioning a user who is not a member is refused
+pub(crate) async fn parse_mentions(
+    conn: &mut PgConnection,
+    content: &str,
+    chat_id: i64,
+    sender_id: i64,
+) -> Result<Vec<MentionRange>, AppError> {
+    if !content.contains('@') {
+        return Ok(vec![]);
+    }
+    let candidates: Vec<MentionCandidate> = sqlx::query_as(
+        r#"
+        SELECT u.fullname AS name,'user'::mention_kind AS kind,u.id AS target_id,u.id=ANY(c.members) AS member
+        FROM chats c
+        JOIN users u ON u.id=ANY(c.members) OR u.ws_id=(SELECT ws_id FROM users WHERE id=$2)
+        WHERE c.id=$1
+        UNION ALL
+        SELECT g.handle,'group',g.id,true
+        FROM chats c
+        JOIN user_groups g ON g.ws_id=c.ws_id OR g.ws_id=(SELECT ws_id FROM users WHERE id=$2)
+        WHERE c.id=$1
+        UNION ALL
+        SELECT 'here','here',NULL,true
+        UNION ALL
+        SELECT 'channel','channel',NULL,true
+        "#,
+    )
+    .bind(chat_id)
+    .bind(sender_id)
+    .fetch_all(conn)
+    .await?;
+
+    let tokens = find_mentions(content, &candidates);
+    if let Some((_, c)) = tokens.iter().find(|(_, c)| !c.member) {
+        return Err(AppError::MessageCreateError(format!(
+            "{} is not a member of this chat",
+            c.name
+        )));
+    }
+    Ok(tokens.into_iter().map(|(range, _)| range).collect())
+}
+
+//store the members mentioned by the ranges, replacing the previous ones.
+//the sender is never mentioned, @here only reaches members online right now
+pub(crate) async fn save_mentions(
+    conn: &mut PgConnection,
+    message_id: i64,
+    chat_id: i64,
+    sender_id: i64,
+    mentions: &[MentionRange],
+) -> Result<(), AppError> {
+    sqlx::query(r#"DELETE FROM message_mentions WHERE message_id=$1"#)
+        .bind(message_id)
+        .execute(&mut *conn)
+        .await?;
+    if mentions.is_empty() {
+        return Ok(());
+    }
+    let targets = |kind: MentionKind| -> Vec<i64> {
+        mentions
+            .iter()
+            .filter(|m| m.kind == kind)
+            .filter_map(|m| m.target_id)
+            .collect()
+    };
+    let has = |kind: MentionKind| mentions.iter().any(|m| m.kind == kind);
+    sqlx::query(
+        r#"
+        INSERT INTO message_mentions(message_id,user_id,kind)
+        SELECT DISTINCT ON (m.user_id) $1,m.user_id,m.kind
+        FROM (
+            SELECT unnest($2::BIGINT[]) AS user_id,'user'::mention_kind AS kind
+            UNION ALL
+            SELECT user_id,'group' FROM user_group_members WHERE group_id=ANY($3)
+            UNION ALL
+            SELECT user_id,'here' FROM user_presence WHERE $4 and connections > 0
+            UNION ALL
+            SELECT unnest(members),'channel' FROM chats WHERE $5 and id=$6
+        ) m
+        JOIN chats c ON c.id=$6
+        WHERE m.user_id=ANY(c.members) and m.user_id <> $7
+        ORDER BY m.user_id,m.kind
+        "#,
+    )
+    .bind(message_id)
+    .bind(targets(MentionKind::User))
+    .bind(targets(MentionKind::Group))
+    .bind(has(MentionKind::Here))
+    .bind(has(MentionKind::Channel))
+    .bind(chat_id)
+    .bind(sender_id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+//a token starts with '@' outside of a word and the longest matching name wins,
+//users win over groups and keywords with the same name
+fn find_mentions<'a>(
+    content: &str,
+    candidates: &'a [MentionCandidate],
+) -> Vec<(MentionRange, &'a MentionCandidate)> {
+    let mut candidates: Vec<_> = candidates.iter().filter(|c| !c.name.is_empty()).collect();
+    candidates.sort_by_key(|c| {
+        (
+            std::cmp::Reverse(c.name.chars().count()),
+            c.kind != MentionKind::User,
+            !c.member,
+        )
+    });
+
+    let chars: Vec<(usize, char)> = content.char_indices().collect();
+    let mut ret = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        let at_word_start = i == 0 || !is_word_char(chars[i - 1].1);
+        if c != '@' || !at_word_start {
+            i += 1;
+            continue;
+        }
+        let rest = &content[pos + 1..];
+        let found = candidates.iter().find(|c| {
+            rest.starts_with(&c.name)
+                && !rest[c.name.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(is_word_char)
+        });
+        match found {
+            Some(candidate) => {
+                let len = candidate.name.chars().count() + 1;
+                ret.push((
+                    MentionRange {
+                        start: i as i32,
+                        end: (i + len) as i32,
+                        kind: candidate.kind,
+                        target_id: candidate.target_id,
+                    },
+                    *candidate,
+                ));
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+    ret
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, kind: MentionKind, target_id: Option<i64>) -> MentionCandidate {
+        MentionCandidate {
+            name: name.to_string(),
+            kind,
+            target_id,
+            member: true,
+        }
+    }
+
+    #[test]
+    fn find_mentions_should_match_longest_name() {
+        let candidates = vec![
+            candidate("kevin", MentionKind::User, Some(1)),
+            candidate("kevin2", MentionKind::User, Some(2)),
+            candidate("Kevin Yang", MentionKind::User, Some(3)),
+            candidate("backend", MentionKind::Group, Some(1)),
+            candidate("here", MentionKind::Here, None),
+        ];
+        let tokens = find_mentions(
+            "héllo @kevin2, @Kevin Yang and @backend @here! mail@kevin @kevinx",
+            &candidates,
+        );
+        let ranges: Vec<_> = tokens
+            .iter()
+            .map(|(r, _)| (r.start, r.end, r.target_id))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (6, 13, Some(2)),
+                (15, 26, Some(3)),
+                (31, 39, Some(1)),
+                (40, 45, None)
+            ]
+        );
+        assert_eq!(tokens[2].0.kind, MentionKind::Group);
+    }
+}
@@ -1,24 +1,94 @@
 use std::str::FromStr;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use utoipa::IntoParams;
+use sqlx::{types::Json, FromRow, PgConnection};
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{AppError, AppState};
 
-use super::ChatFile;
+use super::{
+    audit::{write_audit_log, AuditAction},
+    mention::{parse_mentions, save_mentions},
+    policy::verify_can_post,
+    thread::join_thread,
+    ChatFile,
+};
 
-use core_lib::Message;
+use core_lib::{Message, MessageFormat, User};
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+//limits of a single message, markdown is counted by its source
+const MAX_CONTENT_CHARS: usize = 4000;
+const MAX_MESSAGE_FILES: usize = 10;
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
 pub struct CreateMessage {
     pub files: Vec<String>,
     pub content: String,
+    #[serde(default)]
+    pub format: MessageFormat,
 }
 
-#[derive(Debug, Clone, IntoParams, Deserialize, Serialize)]
+const DEFAULT_MESSAGES_PAGE_SIZE: u64 = 50;
+const MAX_MESSAGES_PAGE_SIZE: u64 = 200;
+
+//pages are ordered by (created_at, id) and always returned newest first.
+//at most one of before, after and around, the latest messages without any
+#[derive(Debug, Clone, Default, IntoParams, Deserialize, Serialize)]
 pub struct ListMessages {
-    pub last_id: Option<u64>,
-    pub page_size: u64,
+    //messages older than this one
+    #[serde(alias = "last_id")]
+    pub before: Option<u64>,
+    //messages newer than this one
+    pub after: Option<u64>,
+    //this message with the messages around it, half a page on each side
+    pub around: Option<u64>,
+    //only messages created in [since, until)
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub page_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, IntoParams, Deserialize, Serialize)]
+pub struct ListMessagesSince {
+    //last seq the client has seen
+    pub seq: i64,
+    pub page_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, ToSchema, Deserialize, Serialize)]
+pub struct MessagesSince {
+    //oldest first, seq of messages hidden to the user are skipped
+    pub messages: Vec<Message>,
+    //seq of the latest message of the chat, or reply of the thread
+    pub last_seq: i64,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateMessage {
+    pub content: String,
+}
+
+//content of a message before one of its edits
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct MessageRevision {
+    pub id: i64,
+    pub message_id: i64,
+    pub content: String,
+    pub edited_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+struct EditableMessage {
+    chat_id: i64,
+    sender_id: i64,
+    content: String,
+    files: Vec<String>,
+    created_at: DateTime<Utc>,
+    archived_at: Option<DateTime<Utc>>,
+    is_member: bool,
 }
 
 impl AppState {
@@ -29,67 +99,889 @@ impl AppState {
         chat_id: u64,
         user_id: u64,
     ) -> Result<Message, AppError> {
-        if input.content.is_empty() {
-            return Err(AppError::MessageCreateError(
-                "content is required".to_string(),
-            ));
+        self.insert_message(input, chat_id, user_id, None).await
+    }
+
+    //replies (with parent_id) are not limited by the posting policy of the chat
+    pub(crate) async fn insert_message(
+        &self,
+        input: CreateMessage,
+        chat_id: u64,
+        user_id: u64,
+        parent_id: Option<i64>,
+    ) -> Result<Message, AppError> {
+        verify_content(&input.content)?;
+        if input.files.len() > MAX_MESSAGE_FILES {
+            return Err(AppError::MessageCreateError(format!(
+                "a message can have at most {} files",
+                MAX_MESSAGE_FILES
+            )));
+        }
+        //check chat_id exists and user_id in this chat
+        let chat = match self
+            .find_member_chat(chat_id as i64, user_id as i64)
+            .await?
+        {
+            Some(chat) => chat,
+            None => {
+                return Err(AppError::MessageCreateError(
+                    "chat not exists or user not in this chat".to_string(),
+                ))
+            }
+        };
+        if chat.archived_at.is_some() {
+            return Err(AppError::ChatArchived(chat_id.to_string()));
         }
         for s in &input.files {
             let base_dir = &self.config.server.base_dir;
             let file = ChatFile::from_str(s)?;
 
-            if !file.path(base_dir).exists() {
+            //files of workspaces outside the chat can't be attached
+            if !file.path(base_dir).exists()
+                || !self.is_chat_in_workspace(&chat, file.ws_id).await?
+            {
                 return Err(AppError::MessageCreateError("file not exists".to_string()));
             }
         }
-        //check chat_id exists and user_id in this chat
-        let chat = self.is_chat_member(chat_id as i64, user_id as i64).await?;
 
-        if !chat {
-            return Err(AppError::MessageCreateError(
-                "chat not exists or user not in this chat".to_string(),
-            ));
+        let mut tx = self.pool.begin().await?;
+        verify_can_post(&mut tx, chat_id as _, user_id as _, parent_id.is_some()).await?;
+        if let Some(parent_id) = parent_id {
+            join_thread(&mut tx, parent_id, user_id as _).await?;
         }
-
-        let pool = &self.pool;
-        let message = sqlx::query_as(
+        let mentions = parse_mentions(&mut tx, &input.content, chat_id as _, user_id as _).await?;
+        let mut message: Message = sqlx::query_as(
             r#"
-            INSERT INTO messages(chat_id,sender_id,content,files)
-            VALUES($1,$2,$3,$4)
-            RETURNING id,chat_id,sender_id,content,files,created_at
+            INSERT INTO messages(chat_id,sender_id,content,files,parent_id,mentions,format)
+            VALUES($1,$2,$3,$4,$5,$6,$7)
+            RETURNING id,chat_id,sender_id,content,files,created_at,edited_at,deleted_at,
+                parent_id,reply_count,reply_participants,last_reply_at,mentions,seq,format
             "#,
         )
         .bind(chat_id as i64)
         .bind(user_id as i64)
         .bind(input.content)
         .bind(input.files)
-        .fetch_one(pool)
+        .bind(parent_id)
+        .bind(Json(&mentions))
+        .bind(input.format)
+        .fetch_one(&mut *tx)
         .await?;
+        save_mentions(&mut tx, message.id, chat_id as _, user_id as _, &mentions).await?;
+        tx.commit().await?;
+        message.render();
         Ok(message)
     }
 
     pub async fn list_messages(
         &self,
         input: ListMessages,
+        chat_id: u64,
+        user: &User,
+    ) -> Result<Vec<Message>, AppError> {
+        let exists: bool = sqlx::query_scalar(
+            r#"SELECT EXISTS(SELECT 1 FROM chats WHERE id=$1 and chat_in_workspace(id,ws_id,$2))"#,
+        )
+        .bind(chat_id as i64)
+        .bind(user.ws_id)
+        .fetch_one(&self.pool)
+        .await?;
+        if !exists {
+            return Err(AppError::NotFound(format!("chat id {}", chat_id)));
+        }
+        self.fetch_message_page(input, chat_id as _, None, user)
+            .await
+    }
 
+    //messages after seq, for clients repairing gaps in the events they received
+    pub async fn list_messages_since(
+        &self,
+        input: ListMessagesSince,
         chat_id: u64,
+        user: &User,
+    ) -> Result<MessagesSince, AppError> {
+        let last_seq: Option<i64> = sqlx::query_scalar(
+            r#"SELECT last_seq FROM chats WHERE id=$1 and chat_in_workspace(id,ws_id,$2)"#,
+        )
+        .bind(chat_id as i64)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(last_seq) = last_seq else {
+            return Err(AppError::NotFound(format!("chat id {}", chat_id)));
+        };
+        self.fetch_messages_since(input, chat_id as _, None, last_seq, user)
+            .await
+    }
+
+    //messages of a chat, or replies of a thread in it, with seq in (input.seq, last_seq]
+    pub(crate) async fn fetch_messages_since(
+        &self,
+        input: ListMessagesSince,
+        chat_id: i64,
+        parent_id: Option<i64>,
+        last_seq: i64,
+        user: &User,
+    ) -> Result<MessagesSince, AppError> {
+        let page_size = input
+            .page_size
+            .unwrap_or(DEFAULT_MESSAGES_PAGE_SIZE)
+            .clamp(1, MAX_MESSAGES_PAGE_SIZE);
+        let mut messages: Vec<Message> = sqlx::query_as(
+            r#"
+            SELECT m.id,m.chat_id,m.sender_id,m.content,m.files,m.created_at,m.edited_at,m.deleted_at,
+                m.parent_id,m.reply_count,m.reply_participants,m.last_reply_at,m.mentions,m.seq,m.format
+            FROM messages m
+            WHERE m.chat_id=$1 and m.parent_id IS NOT DISTINCT FROM $2 and m.seq > $3 and m.seq <= $4
+                and m.created_at >= history_start(m.chat_id,$5)
+                and NOT EXISTS(SELECT 1 FROM user_blocks b WHERE b.blocker_id=$5 and b.blocked_id=m.sender_id)
+            ORDER BY m.seq
+            LIMIT $6
+            "#,
+        )
+        .bind(chat_id)
+        .bind(parent_id)
+        .bind(input.seq)
+        .bind(last_seq)
+        .bind(user.id)
+        .bind(page_size as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        self.attach_reactions(&mut messages).await?;
+        messages.iter_mut().for_each(Message::render);
+        Ok(MessagesSince { messages, last_seq })
+    }
+
+    //a page of the messages of a chat, or of the replies of a thread in it
+    pub(crate) async fn fetch_message_page(
+        &self,
+        input: ListMessages,
+        chat_id: i64,
+        parent_id: Option<i64>,
+        user: &User,
+    ) -> Result<Vec<Message>, AppError> {
+        let page_size = input
+            .page_size
+            .unwrap_or(DEFAULT_MESSAGES_PAGE_SIZE)
+            .clamp(1, MAX_MESSAGES_PAGE_SIZE);
+        let anchors = [input.before, input.after, input.around];
+        if anchors.iter().flatten().count() > 1 {
+            return Err(AppError::ListMessagesError(
+                "only one of before, after and around can be set".to_string(),
+            ));
+        }
+        let range = (input.since, input.until);
+        let mut messages = match anchors {
+            [Some(id), _, _] => {
+                let anchor = self.find_anchor(id, chat_id, parent_id).await?;
+                self.fetch_messages(
+                    chat_id,
+                    parent_id,
+                    user,
+                    range,
+                    Some((anchor, "<")),
+                    page_size,
+                )
+                .await?
+            }
+            [_, Some(id), _] => {
+                let anchor = self.find_anchor(id, chat_id, parent_id).await?;
+                self.fetch_messages(
+                    chat_id,
+                    parent_id,
+                    user,
+                    range,
+                    Some((anchor, ">")),
+                    page_size,
+                )
+                .await?
+            }
+            [_, _, Some(id)] => {
+                let anchor = self.find_anchor(id, chat_id, parent_id).await?;
+                let newer = page_size / 2;
+                let mut messages = self
+                    .fetch_messages(chat_id, parent_id, user, range, Some((anchor, ">")), newer)
+                    .await?;
+                let older = self
+                    .fetch_messages(
+                        chat_id,
+                        parent_id,
+                        user,
+                        range,
+                        Some((anchor, "<=")),
+                        page_size - newer,
+                    )
+                    .await?;
+                messages.extend(older);
+                messages
+            }
+            _ => {
+                self.fetch_messages(chat_id, parent_id, user, range, None, page_size)
+                    .await?
+            }
+        };
+        self.attach_reactions(&mut messages).await?;
+        messages.iter_mut().for_each(Message::render);
+        Ok(messages)
+    }
+
+    //up to limit messages next to the anchor in the direction of the operator,
+    //newest first
+    async fn fetch_messages(
+        &self,
+        chat_id: i64,
+        parent_id: Option<i64>,
+        user: &User,
+        (since, until): (Option<DateTime<Utc>>, Option<DateTime<Utc>>),
+        anchor: Option<((DateTime<Utc>, i64), &str)>,
+        limit: u64,
     ) -> Result<Vec<Message>, AppError> {
-        let last_id = input.last_id.unwrap_or(i64::MAX as _);
-        let pool = &self.pool;
-        let messages = sqlx::query_as(
+        let (cursor, op) = anchor.unzip();
+        let op = op.unwrap_or("<");
+        let order = if op == ">" { "ASC" } else { "DESC" };
+        let sql = format!(
+            r#"
+            SELECT m.id,m.chat_id,m.sender_id,m.content,m.files,m.created_at,m.edited_at,m.deleted_at,
+                m.parent_id,m.reply_count,m.reply_participants,m.last_reply_at,m.mentions,m.seq,m.format
+            FROM messages m
+            WHERE m.chat_id=$1 and m.parent_id IS NOT DISTINCT FROM $2
+                and m.created_at >= history_start(m.chat_id,$3)
+                and NOT EXISTS(SELECT 1 FROM user_blocks b WHERE b.blocker_id=$3 and b.blocked_id=m.sender_id)
+                and ($4::timestamptz IS NULL OR m.created_at >= $4)
+                and ($5::timestamptz IS NULL OR m.created_at < $5)
+                and ($6::timestamptz IS NULL OR (m.created_at,m.id) {op} ($6,$7))
+            ORDER BY m.created_at {order}, m.id {order}
+            LIMIT $8
+            "#
+        );
+        let mut messages: Vec<Message> = sqlx::query_as(&sql)
+            .bind(chat_id)
+            .bind(parent_id)
+            .bind(user.id)
+            .bind(since)
+            .bind(until)
+            .bind(cursor.map(|(created_at, _)| created_at))
+            .bind(cursor.map_or(0, |(_, id)| id))
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        if order == "ASC" {
+            messages.reverse();
+        }
+        Ok(messages)
+    }
+
+    //position of the anchor message, it must be in the listed chat or thread
+    async fn find_anchor(
+        &self,
+        id: u64,
+        chat_id: i64,
+        parent_id: Option<i64>,
+    ) -> Result<(DateTime<Utc>, i64), AppError> {
+        let anchor = sqlx::query_as(
             r#"
-            SELECT id,chat_id,sender_id,content,files,created_at
+            SELECT created_at,id
             FROM messages
-            WHERE chat_id=$1 and id < $2
-            ORDER BY created_at DESC
-            LIMIT $3
+            WHERE id=$1 and chat_id=$2 and parent_id IS NOT DISTINCT FROM $3
             "#,
         )
-        .bind(chat_id as i64)
-        .bind(last_id as i64)
-        .bind(input.page_size as i64)
-        .fetch_all(pool)
+        .bind(id as i64)
+        .bind(chat_id)
+        .bind(parent_id)
+        .fetch_optional(&self.pool)
         .await?;
-        Ok(messages)
+        anchor.ok_or_else(|| AppError::NotFound(format!("message id {}", id)))
+    }
+
+    //only the author can edit, and only within the configured window
+    pub async fn update_message(
+        &self,
+        input: UpdateMessage,
+        id: u64,
+        user: &User,
+    ) -> Result<Message, AppError> {
+        verify_content(&input.content)?;
+        let mut tx = self.pool.begin().await?;
+        let message = lock_message(&mut tx, id, user).await?;
+        if message.sender_id != user.id || !message.is_member {
+            return Err(AppError::PermissionDenied(
+                "only the author can edit a message".to_string(),
+            ));
+        }
+        if message.archived_at.is_some() {
+            return Err(AppError::ChatArchived(message.chat_id.to_string()));
+        }
+        let window = self.config.server.message_edit_window_secs;
+        if (Utc::now() - message.created_at).num_seconds() > window as i64 {
+            return Err(AppError::PermissionDenied(format!(
+                "messages can only be edited within {} seconds",
+                window
+            )));
+        }
+        if message.content == input.content {
+            return Err(AppError::MessageCreateError(
+                "content is unchanged".to_string(),
+            ));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO message_revisions(message_id,content,edited_by)
+            VALUES($1,$2,$3)
+            "#,
+        )
+        .bind(id as i64)
+        .bind(message.content)
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+        let mentions = parse_mentions(&mut tx, &input.content, message.chat_id, user.id).await?;
+        save_mentions(&mut tx, id as _, message.chat_id, user.id, &mentions).await?;
+        let mut message: Message = sqlx::query_as(
+            r#"
+            UPDATE messages
+            SET content=$1,edited_at=now(),mentions=$3
+            WHERE id=$2
+            RETURNING id,chat_id,sender_id,content,files,created_at,edited_at,deleted_at,
+                parent_id,reply_count,reply_participants,last_reply_at,mentions,seq,format
+            "#,
+        )
+        .bind(input.content)
+        .bind(id as i64)
+        .bind(Json(&mentions))
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        message.render();
+        Ok(message)
+    }
+
+    //the author and chat moderators can delete a message, a tombstone without
+    //content is kept in the chat
+    pub async fn delete_message(&self, id: u64, user: &User) -> Result<Message, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let message = lock_message(&mut tx, id, user).await?;
+        let is_author = message.sender_id == user.id && message.is_member;
+        if !is_author
+            && !self
+                .is_chat_moderator(message.chat_id as _, user.id as _)
+                .await?
+        {
+            return Err(AppError::PermissionDenied(
+                "only the author or chat moderators can delete a message".to_string(),
+            ));
+        }
+        if message.archived_at.is_some() {
+            return Err(AppError::ChatArchived(message.chat_id.to_string()));
+        }
+
+        let tombstone = sqlx::query_as(
+            r#"
+            UPDATE messages
+            SET content='',files='{}',mentions='[]',deleted_at=now(),deleted_by=$1
+            WHERE id=$2
+            RETURNING id,chat_id,sender_id,content,files,created_at,edited_at,deleted_at,
+                parent_id,reply_count,reply_participants,last_reply_at,mentions,seq,format
+            "#,
+        )
+        .bind(user.id)
+        .bind(id as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(r#"DELETE FROM message_revisions WHERE message_id=$1"#)
+            .bind(id as i64)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(r#"DELETE FROM pinned_messages WHERE message_id=$1"#)
+            .bind(id as i64)
+            .execute(&mut *tx)
+            .await?;
+        save_mentions(&mut tx, id as _, message.chat_id, message.sender_id, &[]).await?;
+        if !is_author {
+            write_audit_log(
+                &mut tx,
+                message.chat_id,
+                user.id,
+                AuditAction::MessageDeleted,
+                serde_json::json!({ "message_id": id, "sender_id": message.sender_id }),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        //the message is gone already, a file left on disk is not worth an error
+        if let Err(e) = self.remove_unreferenced_files(&message.files).await {
+            warn!("failed to remove files of message {}: {:?}", id, e);
+        }
+        Ok(tombstone)
+    }
+
+    //previous versions of a message, oldest first, visible to chat moderators
+    pub async fn list_message_revisions(
+        &self,
+        id: u64,
+        user: &User,
+    ) -> Result<Vec<MessageRevision>, AppError> {
+        let chat_id: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT m.chat_id
+            FROM messages m
+            JOIN chats c ON c.id=m.chat_id
+            WHERE m.id=$1 and chat_in_workspace(c.id,c.ws_id,$2)
+            "#,
+        )
+        .bind(id as i64)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(chat_id) = chat_id else {
+            return Err(AppError::NotFound(format!("message id {}", id)));
+        };
+        self.verify_chat_moderator(chat_id as _, user.id as _)
+            .await?;
+        let revisions = sqlx::query_as(
+            r#"
+            SELECT id,message_id,content,edited_by,created_at
+            FROM message_revisions
+            WHERE message_id=$1
+            ORDER BY id
+            "#,
+        )
+        .bind(id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(revisions)
+    }
+}
+
+//content of a new or edited message, newlines and tabs are the only control
+//characters allowed
+fn verify_content(content: &str) -> Result<(), AppError> {
+    if content.trim().is_empty() {
+        return Err(AppError::MessageCreateError(
+            "content is required".to_string(),
+        ));
+    }
+    if content.chars().count() > MAX_CONTENT_CHARS {
+        return Err(AppError::MessageCreateError(format!(
+            "content is longer than {} characters",
+            MAX_CONTENT_CHARS
+        )));
+    }
+    if content
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    {
+        return Err(AppError::MessageCreateError(
+            "content has invalid characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+//lock a message that is not deleted yet in the workspace of the user
+async fn lock_message(
+    conn: &mut PgConnection,
+    id: u64,
+    user: &User,
+) -> Result<EditableMessage, AppError> {
+    let message = sqlx::query_as(
+        r#"
+        SELECT m.chat_id,m.sender_id,m.content,m.files,m.created_at,c.archived_at,
+            $2=ANY(c.members) AS is_member
+        FROM messages m
+        JOIN chats c ON c.id=m.chat_id
+        WHERE m.id=$1 and m.deleted_at IS NULL and chat_in_workspace(c.id,c.ws_id,$3)
+        FOR UPDATE OF m
+        "#,
+    )
+    .bind(id as i64)
+    .bind(user.id)
+    .bind(user.ws_id)
+    .fetch_optional(conn)
+    .await?;
+    message.ok_or_else(|| AppError::NotFound(format!("message id {}", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateWorkspaceEmoji;
+    use anyhow::Result;
+    use core_lib::{MentionKind, MentionRange};
+    use sqlx::postgres::PgListener;
+
+    #[tokio::test]
+    async fn test_update_message_should_keep_revisions() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+
+        //chat 2 is general with kevin(admin), kevin2 and kevin3
+        let input = CreateMessage {
+            content: "helo".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        let message = state.create_message(input, 2, 2).await?;
+        assert!(message.edited_at.is_none());
+
+        let edit = |content: &str| UpdateMessage {
+            content: content.to_string(),
+        };
+        let ret = state
+            .update_message(edit("hijacked"), message.id as _, &kevin)
+            .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let ret = state
+            .update_message(edit("helo"), message.id as _, &kevin2)
+            .await;
+        assert!(matches!(ret, Err(AppError::MessageCreateError(_))));
+        let updated = state
+            .update_message(edit("hello"), message.id as _, &kevin2)
+            .await?;
+        assert_eq!(updated.content, "hello");
+        assert!(updated.edited_at.is_some());
+
+        let ret = state.list_message_revisions(message.id as _, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let revisions = state
+            .list_message_revisions(message.id as _, &kevin)
+            .await?;
+        assert_eq!(revisions.len(), 1);
+        assert_eq!(revisions[0].content, "helo");
+        assert_eq!(revisions[0].edited_by, 2);
+
+        //edit window has passed
+        sqlx::query("UPDATE messages SET created_at=now() - interval '1 day' WHERE id=$1")
+            .bind(message.id)
+            .execute(&state.pool)
+            .await?;
+        let ret = state
+            .update_message(edit("hello again"), message.id as _, &kevin2)
+            .await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_message_should_leave_tombstone() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin3 = state
+            .find_user_by_email("kevin3.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let message = |content: &str| CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        //the posted file is also a workspace emoji, it must outlive the message
+        let file = ChatFile::new(1, "party.png", b"party");
+        let path = file.path(&state.config.server.base_dir);
+        tokio::fs::create_dir_all(path.parent().expect("file should have a parent")).await?;
+        tokio::fs::write(&path, b"party").await?;
+        let input = CreateWorkspaceEmoji {
+            name: "party".to_string(),
+            url: file.url(),
+        };
+        state.create_workspace_emoji(input, &kevin).await?;
+        let input = CreateMessage {
+            files: vec![file.url()],
+            ..message("first")
+        };
+        let first = state.create_message(input, 2, 2).await?;
+        let second = state.create_message(message("second"), 2, 2).await?;
+
+        //kevin3 is neither the author nor a moderator
+        let ret = state.delete_message(first.id as _, &kevin3).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let tombstone = state.delete_message(first.id as _, &kevin2).await?;
+        assert!(tombstone.content.is_empty());
+        assert!(path.exists());
+        assert!(tombstone.deleted_at.is_some());
+        let ret = state.delete_message(first.id as _, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        let edit = UpdateMessage {
+            content: "edited".to_string(),
+        };
+        let ret = state.update_message(edit, first.id as _, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        //moderator deletes are audited
+        state.delete_message(second.id as _, &kevin).await?;
+        let logs = state.list_chat_audit_logs(2).await?;
+        assert!(logs
+            .iter()
+            .any(|l| l.action == AuditAction::MessageDeleted && l.user_id == 1));
+
+        let list = ListMessages::default();
+        let messages = state.list_messages(list, 2, &kevin3).await?;
+        assert_eq!(messages.len(), 2);
+        assert!(messages
+            .iter()
+            .all(|m| m.deleted_at.is_some() && m.content.is_empty()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_messages_should_page_both_ways() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let mut ids = Vec::new();
+        for i in 1..=5 {
+            let input = CreateMessage {
+                content: format!("message {}", i),
+                files: vec![],
+                ..Default::default()
+            };
+            ids.push(state.create_message(input, 3, 1).await?);
+        }
+        //the last message is the oldest one by time, order is m4,m3,m2,m1,m5
+        sqlx::query("UPDATE messages SET created_at=$1 - interval '1 minute' WHERE id=$2")
+            .bind(ids[0].created_at)
+            .bind(ids[4].id)
+            .execute(&state.pool)
+            .await?;
+        let [m1, m2, m3, m4, m5] = [0, 1, 2, 3, 4].map(|i| ids[i].id);
+        let list = |input: ListMessages| {
+            let state = state.clone();
+            let kevin = kevin.clone();
+            async move {
+                let messages = state.list_messages(input, 3, &kevin).await?;
+                anyhow::Ok(messages.iter().map(|m| m.id).collect::<Vec<_>>())
+            }
+        };
+        let page = |page_size| ListMessages {
+            page_size: Some(page_size),
+            ..Default::default()
+        };
+
+        assert_eq!(list(page(2)).await?, vec![m4, m3]);
+        let input = ListMessages {
+            before: Some(m3 as _),
+            ..page(2)
+        };
+        assert_eq!(list(input).await?, vec![m2, m1]);
+        let input = ListMessages {
+            before: Some(m1 as _),
+            ..page(2)
+        };
+        assert_eq!(list(input).await?, vec![m5]);
+        let input = ListMessages {
+            after: Some(m1 as _),
+            ..page(2)
+        };
+        assert_eq!(list(input).await?, vec![m3, m2]);
+        let input = ListMessages {
+            around: Some(m2 as _),
+            ..page(3)
+        };
+        assert_eq!(list(input).await?, vec![m3, m2, m1]);
+        let input = ListMessages {
+            since: Some(ids[1].created_at),
+            until: Some(ids[3].created_at),
+            ..page(10)
+        };
+        assert_eq!(list(input).await?, vec![m3, m2]);
+
+        let input = ListMessages {
+            before: Some(m3 as _),
+            after: Some(m1 as _),
+            ..page(2)
+        };
+        let ret = state.list_messages(input, 3, &kevin).await;
+        assert!(matches!(ret, Err(AppError::ListMessagesError(_))));
+        let other = state
+            .create_message(
+                CreateMessage {
+                    content: "elsewhere".to_string(),
+                    files: vec![],
+                    ..Default::default()
+                },
+                2,
+                1,
+            )
+            .await?;
+        let input = ListMessages {
+            around: Some(other.id as _),
+            ..page(2)
+        };
+        let ret = state.list_messages(input, 3, &kevin).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_messages_since_should_follow_seq() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let message = |content: &str| CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        let first = state.create_message(message("one"), 3, 1).await?;
+        assert_eq!(first.seq, Some(1));
+        let reply = state
+            .create_reply(message("reply"), first.id as _, &kevin)
+            .await?;
+        assert_eq!(reply.seq, Some(1));
+        state.create_message(message("two"), 3, 2).await?;
+        state.create_message(message("three"), 3, 1).await?;
+        assert_eq!(
+            state.create_message(message("other"), 2, 1).await?.seq,
+            Some(1)
+        );
+
+        let input = ListMessagesSince {
+            seq: 1,
+            page_size: Some(1),
+        };
+        let ret = state.list_messages_since(input, 3, &kevin).await?;
+        assert_eq!(ret.last_seq, 3);
+        let seqs: Vec<_> = ret.messages.iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, vec![Some(2)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_message_should_render_markdown() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let message = |content: &str, format| CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            format,
+        };
+
+        let ret = state
+            .create_message(
+                message("> see [docs](https://docs.rs)", MessageFormat::Markdown),
+                3,
+                1,
+            )
+            .await?;
+        let html = "<blockquote>\n<p>see <a href=\"https://docs.rs\">docs</a></p>\n</blockquote>\n";
+        assert_eq!(ret.html.as_deref(), Some(html));
+        let ret = state
+            .create_message(message("> plain <b>", MessageFormat::Plain), 3, 1)
+            .await?;
+        assert_eq!(ret.html, None);
+        let messages = state
+            .list_messages(ListMessages::default(), 3, &kevin)
+            .await?;
+        assert_eq!(messages[1].format, MessageFormat::Markdown);
+        assert_eq!(messages[1].html.as_deref(), Some(html));
+
+        for content in [" \n ", "bell\u{7}", &"a".repeat(MAX_CONTENT_CHARS + 1)] {
+            let ret = state
+                .create_message(message(content, MessageFormat::Markdown), 3, 1)
+                .await;
+            assert!(matches!(ret, Err(AppError::MessageCreateError(_))));
+        }
+        let input = CreateMessage {
+            files: vec!["/files/1/a.png".to_string(); MAX_MESSAGE_FILES + 1],
+            ..message("files", MessageFormat::Plain)
+        };
+        let ret = state.create_message(input, 3, 1).await;
+        assert!(matches!(ret, Err(AppError::MessageCreateError(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_message_at_limit_should_notify() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let mut listener = PgListener::connect_with(&state.pool).await?;
+        listener.listen("chat_message_created").await?;
+
+        //three bytes per character and escaped quotes, far over the 8000 bytes
+        //pg_notify takes, the payload only carries the id
+        let content = "好\"".repeat(MAX_CONTENT_CHARS / 2);
+        let input = CreateMessage {
+            content: content.clone(),
+            files: vec![],
+            ..Default::default()
+        };
+        let message = state.create_message(input, 3, 1).await?;
+        assert_eq!(message.content, content);
+        let notification = listener.recv().await?;
+        let payload: serde_json::Value = serde_json::from_str(notification.payload())?;
+        assert_eq!(payload["message_id"], message.id);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_message_should_store_mentions() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let message = |content: &str| CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        let mentioned = |id: i64| {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT user_id FROM message_mentions WHERE message_id=$1 ORDER BY user_id",
+            )
+            .bind(id)
+            .fetch_all(&state.pool)
+        };
+
+        //chat 3 is private with kevin and kevin2
+        let ret = state.create_message(message("hi @kevin3"), 3, 1).await;
+        assert!(matches!(ret, Err(AppError::MessageCreateError(_))));
+        let sent = state.create_message(message("hi @kevin2"), 3, 1).await?;
+        assert_eq!(
+            sent.mentions,
+            vec![MentionRange {
+                start: 3,
+                end: 10,
+                kind: MentionKind::User,
+                target_id: Some(2),
+            }]
+        );
+        assert_eq!(mentioned(sent.id).await?, vec![2]);
+
+        //the sender is not mentioned by @channel
+        let sent = state
+            .create_message(message("@channel ship it"), 2, 1)
+            .await?;
+        assert_eq!(sent.mentions[0].kind, MentionKind::Channel);
+        assert_eq!(mentioned(sent.id).await?, vec![2, 3]);
+
+        let edit = UpdateMessage {
+            content: "ship it".to_string(),
+        };
+        let edited = state.update_message(edit, sent.id as _, &kevin).await?;
+        assert!(edited.mentions.is_empty());
+        assert!(mentioned(sent.id).await?.is_empty());
+        Ok(())
     }
 }
@@ -1,11 +1,48 @@
+mod audit;
+mod block;
 mod chat;
+mod chat_meta;
 mod file;
+mod group;
+mod invite;
+mod member;
+mod mention;
 mod message;
+mod pin;
+mod policy;
+mod reaction;
+mod search;
+mod section;
+mod shared;
+mod sidebar;
+mod thread;
 mod user;
 mod workspace;
-pub use chat::CreateChat;
-pub use message::{CreateMessage, ListMessages};
+pub use audit::{AuditAction, ChatAuditLog};
+pub use block::{BlockReport, BlockUser, BlockedUser};
+pub use chat::{ChatListItem, CreateChat, CreateDirectMessage, ListChats};
+pub use chat_meta::{ChatTopic, UpdateChatDescription, UpdateChatIcon, UpdateChatTopic};
+pub use group::{
+    AddGroupToChat, CreateUserGroup, UpdateUserGroup, UpdateUserGroupMembers, UserGroup,
+};
+pub use invite::{ChatInvite, CreateChatInvite};
+pub use member::{
+    ChatMember, ChatRole, ListChatMembers, MarkChatRead, MemberRole, NotifyPrefs, UpdateMemberRole,
+    UpdateNotifyPrefs,
+};
+pub use message::{
+    CreateMessage, ListMessages, ListMessagesSince, MessageRevision, MessagesSince, UpdateMessage,
+};
+pub use pin::PinnedMessage;
+pub use policy::UpdateChatPolicy;
+pub use reaction::{AddReaction, CreateWorkspaceEmoji, WorkspaceEmoji};
+pub use search::{SearchHit, SearchMessages, SearchOrder, SearchResult};
+pub use section::{CreateSidebarSection, ReorderSidebarSections, UpdateChatSidebar};
 use serde::{Deserialize, Serialize};
+pub use shared::{
+    DisconnectSharedChannel, ShareChat, ShareStatus, SharedChannel, UpdateSharedMembers,
+};
+pub use sidebar::{LastMessage, ListSidebar, SidebarItem};
 pub use user::{CreateUser, SigninUser};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
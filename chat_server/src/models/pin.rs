@@ -0,0 +1,272 @@
+use chrono::{DateTime, Utc};
+use core_lib::{ChatUser, Message, MessagePin, PostPolicy, User};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+use super::{
+    audit::{write_audit_log, AuditAction},
+    ChatRole,
+};
+
+const MAX_PINNED_MESSAGES: i64 = 50;
+
+#[derive(Debug, Clone, ToSchema, Deserialize, Serialize)]
+pub struct PinnedMessage {
+    pub message: Message,
+    pub pinned_by: ChatUser,
+    pub pinned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+struct PinnedMessageRow {
+    #[sqlx(flatten)]
+    message: Message,
+    pinned_at: DateTime<Utc>,
+    pinner_id: i64,
+    pinner_fullname: String,
+    pinner_email: String,
+}
+
+#[derive(Debug, FromRow)]
+struct PinningState {
+    chat_id: i64,
+    archived: bool,
+    role: ChatRole,
+    pin_policy: PostPolicy,
+    is_admin: bool,
+}
+
+impl AppState {
+    //pinning the same message again keeps the first pin
+    pub async fn pin_message(&self, message_id: u64, user: &User) -> Result<MessagePin, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let chat_id = verify_can_pin(&mut tx, message_id, user).await?;
+        let pin: Option<MessagePin> = sqlx::query_as(
+            r#"
+            INSERT INTO pinned_messages(message_id,chat_id,pinned_by)
+            SELECT $1,$2,$3
+            WHERE (SELECT count(*) FROM pinned_messages WHERE chat_id=$2) < $4
+            ON CONFLICT(message_id) DO NOTHING
+            RETURNING message_id,chat_id,pinned_by,pinned_at
+            "#,
+        )
+        .bind(message_id as i64)
+        .bind(chat_id)
+        .bind(user.id)
+        .bind(MAX_PINNED_MESSAGES)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(pin) = pin else {
+            let pinned: Option<MessagePin> = sqlx::query_as(
+                r#"
+                SELECT message_id,chat_id,pinned_by,pinned_at
+                FROM pinned_messages
+                WHERE message_id=$1
+                "#,
+            )
+            .bind(message_id as i64)
+            .fetch_optional(&mut *tx)
+            .await?;
+            return pinned.ok_or_else(|| {
+                AppError::PinError(format!(
+                    "a chat can have at most {} pinned messages",
+                    MAX_PINNED_MESSAGES
+                ))
+            });
+        };
+        write_audit_log(
+            &mut tx,
+            chat_id,
+            user.id,
+            AuditAction::MessagePinned,
+            serde_json::json!({ "message_id": message_id }),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(pin)
+    }
+
+    pub async fn unpin_message(&self, message_id: u64, user: &User) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        let chat_id = verify_can_pin(&mut tx, message_id, user).await?;
+        let pinned_by: Option<i64> = sqlx::query_scalar(
+            r#"DELETE FROM pinned_messages WHERE message_id=$1 RETURNING pinned_by"#,
+        )
+        .bind(message_id as i64)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(pinned_by) = pinned_by else {
+            return Err(AppError::NotFound(format!("pin of message {}", message_id)));
+        };
+        write_audit_log(
+            &mut tx,
+            chat_id,
+            user.id,
+            AuditAction::MessageUnpinned,
+            serde_json::json!({ "message_id": message_id, "pinned_by": pinned_by }),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    //latest pins first, pins of messages hidden to the user are left out
+    pub async fn list_pinned_messages(
+        &self,
+        chat_id: u64,
+        user: &User,
+    ) -> Result<Vec<PinnedMessage>, AppError> {
+        let rows: Vec<PinnedMessageRow> = sqlx::query_as(
+            r#"
+            SELECT m.id,m.chat_id,m.sender_id,m.content,m.files,m.created_at,m.edited_at,m.deleted_at,
+                m.parent_id,m.reply_count,m.reply_participants,m.last_reply_at,m.mentions,m.seq,m.format,
+                p.pinned_at,u.id AS pinner_id,u.fullname AS pinner_fullname,u.email AS pinner_email
+            FROM pinned_messages p
+            JOIN messages m ON m.id=p.message_id
+            JOIN users u ON u.id=p.pinned_by
+            WHERE p.chat_id=$1 and m.created_at >= history_start(p.chat_id,$2)
+                and NOT EXISTS(SELECT 1 FROM user_blocks b WHERE b.blocker_id=$2 and b.blocked_id=m.sender_id)
+            ORDER BY p.pinned_at DESC, p.message_id DESC
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(user.id)
+        .fetch_all(&self.pool)
+        .await?;
+        let (mut messages, pins): (Vec<_>, Vec<_>) = rows
+            .into_iter()
+            .map(|row| {
+                let pinner = ChatUser {
+                    id: row.pinner_id,
+                    fullname: row.pinner_fullname,
+                    email: row.pinner_email,
+                };
+                (row.message, (pinner, row.pinned_at))
+            })
+            .unzip();
+        self.attach_reactions(&mut messages).await?;
+        messages.iter_mut().for_each(Message::render);
+        let pins = messages
+            .into_iter()
+            .zip(pins)
+            .map(|(message, (pinned_by, pinned_at))| PinnedMessage {
+                message,
+                pinned_by,
+                pinned_at,
+            })
+            .collect();
+        Ok(pins)
+    }
+}
+
+//chat id of a message visible to the user, who is allowed to change its pin by the
+//pin policy of the chat. moderators and workspace admins are never limited
+async fn verify_can_pin(
+    conn: &mut PgConnection,
+    message_id: u64,
+    user: &User,
+) -> Result<i64, AppError> {
+    let state: Option<PinningState> = sqlx::query_as(
+        r#"
+        SELECT c.id AS chat_id,c.archived_at IS NOT NULL AS archived,cm.role,c.pin_policy,
+            w.owner_id=cm.user_id AS is_admin
+        FROM messages m
+        JOIN chats c ON c.id=m.chat_id
+        JOIN chat_members cm ON cm.chat_id=c.id and cm.user_id=$2
+        JOIN workspaces w ON w.id=c.ws_id
+        WHERE m.id=$1 and m.deleted_at IS NULL and chat_in_workspace(c.id,c.ws_id,$3)
+            and m.created_at >= history_start(c.id,$2)
+        "#,
+    )
+    .bind(message_id as i64)
+    .bind(user.id)
+    .bind(user.ws_id)
+    .fetch_optional(conn)
+    .await?;
+    let Some(state) = state else {
+        return Err(AppError::NotFound(format!("message id {}", message_id)));
+    };
+    if state.archived {
+        return Err(AppError::ChatArchived(state.chat_id.to_string()));
+    }
+    let is_moderator = state.role == ChatRole::Moderator || state.is_admin;
+    if state.pin_policy == PostPolicy::Moderators && state.role != ChatRole::Poster && !is_moderator
+    {
+        return Err(AppError::PermissionDenied(
+            "only moderators and designated posters can pin messages in this chat".to_string(),
+        ));
+    }
+    Ok(state.chat_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateMessage, UpdateChatPolicy};
+    use anyhow::Result;
+
+    fn message(content: &str) -> CreateMessage {
+        CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pins_should_follow_pin_policy() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let alice = state.find_user_by_email("alice@acme.org").await?.unwrap();
+
+        //chat 2 is general with kevin(admin), kevin2 and kevin3
+        let first = state.create_message(message("agenda"), 2, 1).await?;
+        let second = state.create_message(message("notes"), 2, 3).await?;
+        let pin = state.pin_message(first.id as _, &kevin2).await?;
+        assert_eq!(pin.pinned_by, kevin2.id);
+        let again = state.pin_message(first.id as _, &kevin).await?;
+        assert_eq!(again.pinned_by, kevin2.id);
+        state.pin_message(second.id as _, &kevin).await?;
+        let ret = state.pin_message(first.id as _, &alice).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        let pins = state.list_pinned_messages(2, &kevin2).await?;
+        let ids: Vec<_> = pins.iter().map(|p| p.message.id).collect();
+        assert_eq!(ids, vec![second.id, first.id]);
+        assert_eq!(pins[1].pinned_by.fullname, "kevin2");
+
+        let input = UpdateChatPolicy {
+            pin_policy: Some(PostPolicy::Moderators),
+            ..Default::default()
+        };
+        state.update_chat_policy(input, 2, &kevin).await?;
+        let ret = state.unpin_message(first.id as _, &kevin2).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        state.unpin_message(first.id as _, &kevin).await?;
+        let ret = state.unpin_message(first.id as _, &kevin).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        //deleted messages lose their pin
+        state.delete_message(second.id as _, &kevin).await?;
+        assert!(state.list_pinned_messages(2, &kevin).await?.is_empty());
+
+        let logs = state.list_chat_audit_logs(2).await?;
+        assert!(logs
+            .iter()
+            .any(|l| l.action == AuditAction::MessagePinned && l.user_id == kevin2.id));
+        assert!(logs
+            .iter()
+            .any(|l| l.action == AuditAction::MessageUnpinned && l.user_id == kevin.id));
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use chrono::{DateTime, Utc};
+use core_lib::{ChatPolicy, HistoryVisibility, PostPolicy, User};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+use super::{
+    audit::{write_audit_log, AuditAction},
+    ChatRole,
+};
+
+const MAX_SLOW_MODE_SECS: i32 = 6 * 3600;
+const MAX_HISTORY_DAYS: i32 = 3650;
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateChatPolicy {
+    pub post_policy: Option<PostPolicy>,
+    pub slow_mode_secs: Option<i32>,
+    pub history_visibility: Option<HistoryVisibility>,
+    //required when history_visibility is last_days
+    pub history_days: Option<i32>,
+    pub pin_policy: Option<PostPolicy>,
+}
+
+#[derive(Debug, FromRow)]
+struct PostingState {
+    role: ChatRole,
+    post_policy: PostPolicy,
+    slow_mode_secs: i32,
+    is_admin: bool,
+    last_posted_at: Option<DateTime<Utc>>,
+}
+
+impl AppState {
+    pub async fn get_chat_policy(&self, chat_id: u64, ws_id: u64) -> Result<ChatPolicy, AppError> {
+        let policy = sqlx::query_as(
+            r#"
+            SELECT id AS chat_id,post_policy,slow_mode_secs,history_visibility,history_days,pin_policy
+            FROM chats
+            WHERE id=$1 and chat_in_workspace(id,ws_id,$2)
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(ws_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        policy.ok_or_else(|| AppError::NotFound(format!("chat id {}", chat_id)))
+    }
+
+    pub async fn update_chat_policy(
+        &self,
+        input: UpdateChatPolicy,
+        chat_id: u64,
+        user: &User,
+    ) -> Result<ChatPolicy, AppError> {
+        self.verify_chat_moderator(chat_id, user.id as _).await?;
+        self.verify_chat_writable(chat_id as _, user.ws_id as _)
+            .await?;
+        if input
+            .slow_mode_secs
+            .is_some_and(|secs| !(0..=MAX_SLOW_MODE_SECS).contains(&secs))
+        {
+            return Err(AppError::UpdateChatError(format!(
+                "slow_mode_secs must be between 0 and {}",
+                MAX_SLOW_MODE_SECS
+            )));
+        }
+        if input
+            .history_days
+            .is_some_and(|days| !(1..=MAX_HISTORY_DAYS).contains(&days))
+        {
+            return Err(AppError::UpdateChatError(format!(
+                "history_days must be between 1 and {}",
+                MAX_HISTORY_DAYS
+            )));
+        }
+        if input.history_visibility == Some(HistoryVisibility::LastDays)
+            && input.history_days.is_none()
+        {
+            return Err(AppError::UpdateChatError(
+                "history_days is required for last_days visibility".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let policy = sqlx::query_as(
+            r#"
+            UPDATE chats
+            SET post_policy=coalesce($1,post_policy),slow_mode_secs=coalesce($2,slow_mode_secs),
+                history_visibility=coalesce($3,history_visibility),history_days=coalesce($4,history_days),
+                pin_policy=coalesce($5,pin_policy)
+            WHERE id=$6
+            RETURNING id AS chat_id,post_policy,slow_mode_secs,history_visibility,history_days,pin_policy
+            "#,
+        )
+        .bind(input.post_policy)
+        .bind(input.slow_mode_secs)
+        .bind(input.history_visibility)
+        .bind(input.history_days)
+        .bind(input.pin_policy)
+        .bind(chat_id as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+        write_audit_log(
+            &mut tx,
+            chat_id as _,
+            user.id,
+            AuditAction::PolicyChanged,
+            serde_json::json!({
+                "post_policy": input.post_policy,
+                "slow_mode_secs": input.slow_mode_secs,
+                "history_visibility": input.history_visibility,
+                "history_days": input.history_days,
+                "pin_policy": input.pin_policy,
+            }),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(policy)
+    }
+}
+
+//run in the transaction inserting the message, the member row stays locked so
+//concurrent messages of the same member can't slip through slow mode.
+//everyone can reply in threads, slow mode still applies to replies
+pub(crate) async fn verify_can_post(
+    conn: &mut PgConnection,
+    chat_id: i64,
+    user_id: i64,
+    is_reply: bool,
+) -> Result<(), AppError> {
+    let state: Option<PostingState> = sqlx::query_as(
+        r#"
+        SELECT cm.role,c.post_policy,c.slow_mode_secs,w.owner_id=cm.user_id AS is_admin,
+            (SELECT max(created_at) FROM messages WHERE chat_id=$1 and sender_id=$2) AS last_posted_at
+        FROM chat_members cm
+        JOIN chats c ON c.id=cm.chat_id
+        JOIN workspaces w ON w.id=c.ws_id
+        WHERE cm.chat_id=$1 and cm.user_id=$2
+        FOR UPDATE OF cm
+        "#,
+    )
+    .bind(chat_id)
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?;
+    let Some(state) = state else {
+        return Err(AppError::MessageCreateError(
+            "chat not exists or user not in this chat".to_string(),
+        ));
+    };
+    //moderators are not limited by the policy
+    if state.role == ChatRole::Moderator || state.is_admin {
+        return Ok(());
+    }
+    if state.post_policy == PostPolicy::Moderators && state.role != ChatRole::Poster && !is_reply {
+        return Err(AppError::PostNotAllowed(
+            "only moderators and designated posters can post in this chat".to_string(),
+        ));
+    }
+    if let (secs @ 1.., Some(last)) = (state.slow_mode_secs, state.last_posted_at) {
+        let elapsed = (Utc::now() - last).num_seconds();
+        if elapsed < secs as i64 {
+            return Err(AppError::SlowMode((secs as i64 - elapsed) as u64));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateChatInvite, CreateMessage, ListMessages, UpdateMemberRole};
+    use anyhow::Result;
+
+    fn message(content: &str) -> CreateMessage {
+        CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_posting_policy_should_be_enforced() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+
+        //chat 2 is general with kevin(admin), kevin2 and kevin3
+        let input = UpdateChatPolicy {
+            post_policy: Some(PostPolicy::Moderators),
+            ..Default::default()
+        };
+        let ret = state.update_chat_policy(input, 2, &kevin).await?;
+        assert_eq!(ret.post_policy, PostPolicy::Moderators);
+        let ret = state.create_message(message("hi"), 2, 2).await;
+        assert!(matches!(ret, Err(AppError::PostNotAllowed(_))));
+        state.create_message(message("announcement"), 2, 1).await?;
+
+        let role = UpdateMemberRole {
+            user_id: 2,
+            role: ChatRole::Poster,
+        };
+        state.update_member_role(role, 2, 1).await?;
+        state.create_message(message("hi"), 2, 2).await?;
+
+        let input = UpdateChatPolicy {
+            post_policy: Some(PostPolicy::Everyone),
+            slow_mode_secs: Some(60),
+            ..Default::default()
+        };
+        let ret = state.update_chat_policy(input, 2, &kevin).await?;
+        assert_eq!(ret.slow_mode_secs, 60);
+        let ret = state.create_message(message("again"), 2, 2).await;
+        assert!(matches!(ret, Err(AppError::SlowMode(secs)) if secs > 0 && secs <= 60));
+        //first message of kevin3 and moderators are not limited
+        state.create_message(message("hello"), 2, 3).await?;
+        state.create_message(message("again"), 2, 1).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_history_visibility_should_hide_old_messages() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin3 = state
+            .find_user_by_email("kevin3.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+
+        //chat 3 is private with kevin and kevin2
+        let old = state.create_message(message("old"), 3, 1).await?;
+        let input = UpdateChatPolicy {
+            history_visibility: Some(HistoryVisibility::SinceJoin),
+            ..Default::default()
+        };
+        state.update_chat_policy(input, 3, &kevin).await?;
+        let invite = state
+            .create_chat_invite(CreateChatInvite::default(), 3, &kevin)
+            .await?;
+        state.redeem_chat_invite(&invite.token, &kevin3).await?;
+        let new = state.create_message(message("new"), 3, 1).await?;
+
+        let messages = state
+            .list_messages(ListMessages::default(), 3, &kevin3)
+            .await?;
+        let ids: Vec<_> = messages.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![new.id]);
+        let messages = state
+            .list_messages(ListMessages::default(), 3, &kevin)
+            .await?;
+        assert_eq!(messages.len(), 2);
+
+        let input = UpdateChatPolicy {
+            history_visibility: Some(HistoryVisibility::LastDays),
+            ..Default::default()
+        };
+        let ret = state.update_chat_policy(input, 3, &kevin).await;
+        assert!(matches!(ret, Err(AppError::UpdateChatError(_))));
+        let input = UpdateChatPolicy {
+            history_visibility: Some(HistoryVisibility::LastDays),
+            history_days: Some(1),
+            ..Default::default()
+        };
+        state.update_chat_policy(input, 3, &kevin).await?;
+        sqlx::query("UPDATE messages SET created_at=now() - interval '2 days' WHERE id=$1")
+            .bind(old.id)
+            .execute(&state.pool)
+            .await?;
+        let messages = state
+            .list_messages(ListMessages::default(), 3, &kevin)
+            .await?;
+        let ids: Vec<_> = messages.iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![new.id]);
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+use std::{collections::HashMap, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use core_lib::{Message, Reaction, User};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+use super::ChatFile;
+
+const MAX_EMOJI_NAME_LEN: usize = 32;
+//unicode emoji may be sequences joined by zero width joiners
+const MAX_UNICODE_EMOJI_CHARS: usize = 16;
+//distinct emoji on a single message
+const MAX_MESSAGE_REACTIONS: i64 = 50;
+
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct WorkspaceEmoji {
+    pub id: i64,
+    pub ws_id: i64,
+    //used in reactions as :name:
+    pub name: String,
+    pub url: String,
+    pub created_by: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct CreateWorkspaceEmoji {
+    pub name: String,
+    //url of an uploaded file of the workspace
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct AddReaction {
+    //unicode emoji or :name: of a custom emoji
+    pub emoji: String,
+}
+
+#[derive(Debug, FromRow)]
+struct MessageReactions {
+    message_id: i64,
+    #[sqlx(flatten)]
+    reaction: Reaction,
+}
+
+#[derive(Debug, FromRow)]
+struct ReactableMessage {
+    chat_ws_id: i64,
+    archived: bool,
+}
+
+impl AppState {
+    pub async fn list_workspace_emojis(&self, ws_id: u64) -> Result<Vec<WorkspaceEmoji>, AppError> {
+        let emojis = sqlx::query_as(
+            r#"
+            SELECT id,ws_id,name,url,created_by,created_at
+            FROM workspace_emojis
+            WHERE ws_id=$1
+            ORDER BY name
+            "#,
+        )
+        .bind(ws_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(emojis)
+    }
+
+    pub async fn create_workspace_emoji(
+        &self,
+        input: CreateWorkspaceEmoji,
+        user: &User,
+    ) -> Result<WorkspaceEmoji, AppError> {
+        verify_emoji_name(&input.name)?;
+        let file = ChatFile::from_str(&input.url)?;
+        if file.ws_id != user.ws_id || !file.path(&self.config.server.base_dir).exists() {
+            return Err(AppError::ReactionError("emoji file not exists".to_string()));
+        }
+        let emoji = sqlx::query_as(
+            r#"
+            INSERT INTO workspace_emojis(ws_id,name,url,created_by)
+            VALUES($1,$2,$3,$4)
+            RETURNING id,ws_id,name,url,created_by,created_at
+            "#,
+        )
+        .bind(user.ws_id)
+        .bind(&input.name)
+        .bind(&input.url)
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                AppError::ReactionError(format!("emoji {} already exists", input.name))
+            }
+            e => e.into(),
+        })?;
+        Ok(emoji)
+    }
+
+    //the creator of an emoji and the workspace admin can delete it, existing
+    //reactions with it are kept
+    pub async fn delete_workspace_emoji(&self, id: u64, user: &User) -> Result<(), AppError> {
+        let created_by: Option<i64> = sqlx::query_scalar(
+            r#"SELECT created_by FROM workspace_emojis WHERE id=$1 and ws_id=$2"#,
+        )
+        .bind(id as i64)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(created_by) = created_by else {
+            return Err(AppError::NotFound(format!("emoji {}", id)));
+        };
+        if created_by != user.id
+            && !self
+                .is_workspace_admin(user.ws_id as _, user.id as _)
+                .await?
+        {
+            return Err(AppError::PermissionDenied(
+                "only the creator of the emoji or workspace admin can delete it".to_string(),
+            ));
+        }
+        sqlx::query(r#"DELETE FROM workspace_emojis WHERE id=$1"#)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    //every member can react, the posting policy of the chat doesn't apply
+    pub async fn add_reaction(
+        &self,
+        input: AddReaction,
+        message_id: u64,
+        user: &User,
+    ) -> Result<Vec<Reaction>, AppError> {
+        let message = self.find_reactable_message(message_id, user).await?;
+        if let Some(name) = custom_emoji_name(&input.emoji) {
+            let exists: bool = sqlx::query_scalar(
+                r#"
+                SELECT EXISTS(SELECT 1 FROM workspace_emojis WHERE name=$1 and ws_id IN ($2,$3))
+                "#,
+            )
+            .bind(name)
+            .bind(user.ws_id)
+            .bind(message.chat_ws_id)
+            .fetch_one(&self.pool)
+            .await?;
+            if !exists {
+                return Err(AppError::ReactionError(format!(
+                    "emoji {} not exists",
+                    input.emoji
+                )));
+            }
+        } else if !is_unicode_emoji(&input.emoji) {
+            return Err(AppError::ReactionError(format!(
+                "{} is not an emoji",
+                input.emoji
+            )));
+        }
+
+        let ret = sqlx::query(
+            r#"
+            INSERT INTO message_reactions(message_id,user_id,emoji)
+            SELECT $1,$2,$3
+            WHERE EXISTS(SELECT 1 FROM message_reactions WHERE message_id=$1 and emoji=$3)
+                OR (SELECT count(DISTINCT emoji) FROM message_reactions WHERE message_id=$1) < $4
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(message_id as i64)
+        .bind(user.id)
+        .bind(&input.emoji)
+        .bind(MAX_MESSAGE_REACTIONS)
+        .execute(&self.pool)
+        .await?;
+        if ret.rows_affected() == 0 && !self.has_reacted(message_id, user.id, &input.emoji).await? {
+            return Err(AppError::ReactionError(format!(
+                "a message can have at most {} different reactions",
+                MAX_MESSAGE_REACTIONS
+            )));
+        }
+        self.list_reactions(message_id).await
+    }
+
+    pub async fn remove_reaction(
+        &self,
+        emoji: &str,
+        message_id: u64,
+        user: &User,
+    ) -> Result<Vec<Reaction>, AppError> {
+        self.find_reactable_message(message_id, user).await?;
+        let ret = sqlx::query(
+            r#"DELETE FROM message_reactions WHERE message_id=$1 and user_id=$2 and emoji=$3"#,
+        )
+        .bind(message_id as i64)
+        .bind(user.id)
+        .bind(emoji)
+        .execute(&self.pool)
+        .await?;
+        if ret.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("reaction {}", emoji)));
+        }
+        self.list_reactions(message_id).await
+    }
+
+    pub async fn list_reactions(&self, message_id: u64) -> Result<Vec<Reaction>, AppError> {
+        let mut reactions = self.fetch_reactions(&[message_id as i64]).await?;
+        Ok(reactions.remove(&(message_id as i64)).unwrap_or_default())
+    }
+
+    //fill reactions of listed messages
+    pub(crate) async fn attach_reactions(&self, messages: &mut [Message]) -> Result<(), AppError> {
+        let ids: Vec<i64> = messages.iter().map(|m| m.id).collect();
+        let mut reactions = self.fetch_reactions(&ids).await?;
+        for message in messages {
+            message.reactions = reactions.remove(&message.id).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    async fn fetch_reactions(
+        &self,
+        message_ids: &[i64],
+    ) -> Result<HashMap<i64, Vec<Reaction>>, AppError> {
+        let rows: Vec<MessageReactions> = sqlx::query_as(
+            r#"
+            SELECT message_id,emoji,count(*) AS count,
+                array_agg(user_id ORDER BY created_at) AS user_ids
+            FROM message_reactions
+            WHERE message_id=ANY($1)
+            GROUP BY message_id,emoji
+            ORDER BY message_id,min(created_at)
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut reactions: HashMap<i64, Vec<Reaction>> = HashMap::new();
+        for row in rows {
+            reactions
+                .entry(row.message_id)
+                .or_default()
+                .push(row.reaction);
+        }
+        Ok(reactions)
+    }
+
+    async fn has_reacted(
+        &self,
+        message_id: u64,
+        user_id: i64,
+        emoji: &str,
+    ) -> Result<bool, AppError> {
+        let ret = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM message_reactions WHERE message_id=$1 and user_id=$2 and emoji=$3
+            )
+            "#,
+        )
+        .bind(message_id as i64)
+        .bind(user_id)
+        .bind(emoji)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(ret)
+    }
+
+    //a message that is not deleted and visible to a member of its chat
+    async fn find_reactable_message(
+        &self,
+        message_id: u64,
+        user: &User,
+    ) -> Result<ReactableMessage, AppError> {
+        let message: Option<ReactableMessage> = sqlx::query_as(
+            r#"
+            SELECT c.ws_id AS chat_ws_id,c.archived_at IS NOT NULL AS archived
+            FROM messages m
+            JOIN chats c ON c.id=m.chat_id
+            JOIN chat_members cm ON cm.chat_id=c.id and cm.user_id=$2
+            WHERE m.id=$1 and m.deleted_at IS NULL and chat_in_workspace(c.id,c.ws_id,$3)
+                and m.created_at >= history_start(c.id,$2)
+            "#,
+        )
+        .bind(message_id as i64)
+        .bind(user.id)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        match message {
+            Some(message) if message.archived => {
+                Err(AppError::ChatArchived(format!("message {}", message_id)))
+            }
+            Some(message) => Ok(message),
+            None => Err(AppError::NotFound(format!("message id {}", message_id))),
+        }
+    }
+}
+
+//lowercase letters, digits, '-' and '_'
+fn verify_emoji_name(name: &str) -> Result<(), AppError> {
+    let valid = (1..=MAX_EMOJI_NAME_LEN).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::ReactionError(format!(
+            "emoji name must be 1 to {} lowercase letters, digits, '-' or '_'",
+            MAX_EMOJI_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+//name of a custom emoji written as :name:
+fn custom_emoji_name(emoji: &str) -> Option<&str> {
+    emoji
+        .strip_prefix(':')
+        .and_then(|s| s.strip_suffix(':'))
+        .filter(|s| verify_emoji_name(s).is_ok())
+}
+
+//no text allowed, ascii is only used in keycap sequences like 1️⃣
+fn is_unicode_emoji(emoji: &str) -> bool {
+    let count = emoji.chars().count();
+    (1..=MAX_UNICODE_EMOJI_CHARS).contains(&count)
+        && !emoji.is_ascii()
+        && emoji.chars().all(|c| {
+            !c.is_whitespace()
+                && !c.is_control()
+                && (!c.is_ascii() || c.is_ascii_digit() || c == '#' || c == '*')
+                && !c.is_alphabetic()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateMessage, ListMessages};
+    use anyhow::Result;
+
+    #[test]
+    fn unicode_emoji_should_be_recognized() {
+        assert!(is_unicode_emoji("👍"));
+        assert!(is_unicode_emoji("👩‍💻"));
+        assert!(is_unicode_emoji("1️⃣"));
+        assert!(!is_unicode_emoji("ok"));
+        assert!(!is_unicode_emoji("中"));
+        assert!(!is_unicode_emoji("👍 ok"));
+        assert_eq!(custom_emoji_name(":party-parrot:"), Some("party-parrot"));
+        assert_eq!(custom_emoji_name(":Party:"), None);
+    }
+
+    #[tokio::test]
+    async fn test_reactions_should_be_aggregated() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let alice = state.find_user_by_email("alice@acme.org").await?.unwrap();
+
+        let file = ChatFile::new(1, "party.png", b"party");
+        let path = file.path(&state.config.server.base_dir);
+        tokio::fs::create_dir_all(path.parent().expect("file should have a parent")).await?;
+        tokio::fs::write(&path, b"party").await?;
+        let input = CreateWorkspaceEmoji {
+            name: "party".to_string(),
+            url: file.url(),
+        };
+        let emoji = state.create_workspace_emoji(input.clone(), &kevin).await?;
+        let ret = state.create_workspace_emoji(input, &kevin).await;
+        assert!(matches!(ret, Err(AppError::ReactionError(_))));
+        assert_eq!(state.list_workspace_emojis(1).await?.len(), 1);
+
+        let input = CreateMessage {
+            content: "shipped".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        let message = state.create_message(input, 2, 2).await?;
+        let react = |emoji: &str| AddReaction {
+            emoji: emoji.to_string(),
+        };
+        state
+            .add_reaction(react("👍"), message.id as _, &kevin)
+            .await?;
+        state
+            .add_reaction(react(":party:"), message.id as _, &kevin)
+            .await?;
+        let reactions = state
+            .add_reaction(react("👍"), message.id as _, &kevin2)
+            .await?;
+        assert_eq!(reactions.len(), 2);
+        assert_eq!(reactions[0].emoji, "👍");
+        assert_eq!(reactions[0].count, 2);
+        assert_eq!(reactions[0].user_ids, vec![1, 2]);
+        let ret = state
+            .add_reaction(react(":nope:"), message.id as _, &kevin2)
+            .await;
+        assert!(matches!(ret, Err(AppError::ReactionError(_))));
+        let ret = state
+            .add_reaction(react("👍"), message.id as _, &alice)
+            .await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        let reactions = state.remove_reaction("👍", message.id as _, &kevin).await?;
+        //order follows the first remaining reaction of each emoji
+        assert_eq!(reactions[0].emoji, ":party:");
+        assert_eq!(reactions[1].user_ids, vec![2]);
+        let list = ListMessages::default();
+        let messages = state.list_messages(list, 2, &kevin2).await?;
+        assert_eq!(messages[0].reactions.len(), 2);
+
+        state.delete_workspace_emoji(emoji.id as _, &kevin).await?;
+        let ret = state
+            .add_reaction(react(":party:"), message.id as _, &kevin2)
+            .await;
+        assert!(matches!(ret, Err(AppError::ReactionError(_))));
+        Ok(())
+    }
+}
@@ -0,0 +1,363 @@
+use chrono::{Days, NaiveDate};
+use core_lib::{Message, User};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{AppError, AppState};
+
+const DEFAULT_SEARCH_PAGE_SIZE: u64 = 20;
+const MAX_SEARCH_PAGE_SIZE: u64 = 100;
+const MAX_QUERY_LEN: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchOrder {
+    //best matches first, newest first when there are no search terms
+    #[default]
+    Relevance,
+    Date,
+}
+
+#[derive(Debug, Clone, Default, IntoParams, Deserialize, Serialize)]
+pub struct SearchMessages {
+    //terms, "exact phrases" and filters: from:@name in:#chat has:file|link|pin
+    //before:YYYY-MM-DD after:YYYY-MM-DD
+    pub q: String,
+    pub order: Option<SearchOrder>,
+    //next_cursor of the previous page
+    pub cursor: Option<String>,
+    pub page_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, ToSchema, Deserialize, Serialize)]
+pub struct SearchHit {
+    pub message: Message,
+    pub chat_name: Option<String>,
+    //html escaped content with matches wrapped in <mark>
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, ToSchema, Deserialize, Serialize)]
+pub struct SearchResult {
+    pub hits: Vec<SearchHit>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct SearchQuery {
+    //free text for websearch_to_tsquery, phrases keep their quotes
+    text: String,
+    from: Vec<String>,
+    chats: Vec<String>,
+    has_file: bool,
+    has_link: bool,
+    has_pin: bool,
+    before: Option<NaiveDate>,
+    after: Option<NaiveDate>,
+}
+
+#[derive(Debug, FromRow)]
+struct SearchRow {
+    #[sqlx(flatten)]
+    message: Message,
+    chat_name: Option<String>,
+    snippet: String,
+    sort_key: f64,
+}
+
+impl AppState {
+    //messages of the chats the user is a member of, paginated by (sort key, id)
+    pub async fn search_messages(
+        &self,
+        input: SearchMessages,
+        user: &User,
+    ) -> Result<SearchResult, AppError> {
+        let query = parse_search_query(&input.q)?;
+        let page_size = input
+            .page_size
+            .unwrap_or(DEFAULT_SEARCH_PAGE_SIZE)
+            .clamp(1, MAX_SEARCH_PAGE_SIZE);
+        let order = input.order.unwrap_or_default();
+        let cursor = input.cursor.as_deref().map(parse_cursor).transpose()?;
+        let from: Vec<String> = query
+            .from
+            .iter()
+            .map(|name| match name.as_str() {
+                "me" => user.fullname.to_lowercase(),
+                name => name.to_lowercase(),
+            })
+            .collect();
+        let chats: Vec<String> = query.chats.iter().map(|c| c.to_lowercase()).collect();
+        let day_start = |date: NaiveDate| date.and_hms_opt(0, 0, 0).map(|d| d.and_utc());
+        let before = query.before.and_then(day_start);
+        let after = query
+            .after
+            .and_then(|d| d.checked_add_days(Days::new(1)))
+            .and_then(day_start);
+
+        let rows: Vec<SearchRow> = sqlx::query_as(
+            r#"
+            WITH hits AS (
+                SELECT m.id,m.chat_id,m.sender_id,m.content,m.files,m.created_at,m.edited_at,m.deleted_at,
+                    m.parent_id,m.reply_count,m.reply_participants,m.last_reply_at,m.mentions,m.seq,m.format,
+                    c.name AS chat_name,
+                    CASE WHEN $3 THEN extract(epoch FROM m.created_at)::float8
+                        ELSE ts_rank(to_tsvector('english',m.content),q.query)::float8 END AS sort_key
+                FROM messages m
+                JOIN chats c ON c.id=m.chat_id
+                JOIN chat_members cm ON cm.chat_id=c.id and cm.user_id=$1
+                CROSS JOIN websearch_to_tsquery('english',$4) AS q(query)
+                WHERE chat_in_workspace(c.id,c.ws_id,$2) and m.deleted_at IS NULL
+                    and m.created_at >= history_start(c.id,$1)
+                    and NOT EXISTS(SELECT 1 FROM user_blocks b WHERE b.blocker_id=$1 and b.blocked_id=m.sender_id)
+                    and ($4='' OR to_tsvector('english',m.content) @@ q.query)
+                    and (cardinality($5::text[])=0 OR m.sender_id IN (SELECT id FROM users WHERE lower(fullname)=ANY($5)))
+                    and (cardinality($6::text[])=0 OR lower(c.name)=ANY($6))
+                    and (NOT $7 OR cardinality(m.files) > 0)
+                    and (NOT $8 OR m.content ~* 'https?://')
+                    and (NOT $9 OR EXISTS(SELECT 1 FROM pinned_messages p WHERE p.message_id=m.id))
+                    and ($10::timestamptz IS NULL OR m.created_at < $10)
+                    and ($11::timestamptz IS NULL OR m.created_at >= $11)
+            )
+            SELECT h.*,
+                ts_headline('english',
+                    replace(replace(replace(h.content,'&','&amp;'),'<','&lt;'),'>','&gt;'),
+                    websearch_to_tsquery('english',$4),
+                    'StartSel=<mark>,StopSel=</mark>,MaxFragments=2,MaxWords=30,MinWords=10') AS snippet
+            FROM hits h
+            WHERE $12::float8 IS NULL OR (h.sort_key,h.id) < ($12,$13)
+            ORDER BY h.sort_key DESC, h.id DESC
+            LIMIT $14
+            "#,
+        )
+        .bind(user.id)
+        .bind(user.ws_id)
+        .bind(order == SearchOrder::Date)
+        .bind(&query.text)
+        .bind(&from)
+        .bind(&chats)
+        .bind(query.has_file)
+        .bind(query.has_link)
+        .bind(query.has_pin)
+        .bind(before)
+        .bind(after)
+        .bind(cursor.map(|(key, _)| key))
+        .bind(cursor.map_or(i64::MAX, |(_, id)| id))
+        .bind(page_size as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let next_cursor = match rows.last() {
+            Some(row) if rows.len() as u64 == page_size => {
+                Some(format!("{}_{}", row.sort_key, row.message.id))
+            }
+            _ => None,
+        };
+        let (mut messages, rest): (Vec<_>, Vec<_>) = rows
+            .into_iter()
+            .map(|row| (row.message, (row.chat_name, row.snippet)))
+            .unzip();
+        self.attach_reactions(&mut messages).await?;
+        messages.iter_mut().for_each(Message::render);
+        let hits = messages
+            .into_iter()
+            .zip(rest)
+            .map(|(message, (chat_name, snippet))| SearchHit {
+                message,
+                chat_name,
+                snippet,
+            })
+            .collect();
+        Ok(SearchResult { hits, next_cursor })
+    }
+}
+
+//split the query into free text and filters, a filter value may be quoted
+//like from:@"Kevin Yang"
+fn parse_search_query(q: &str) -> Result<SearchQuery, AppError> {
+    if q.chars().count() > MAX_QUERY_LEN {
+        return Err(AppError::SearchError(format!(
+            "query is longer than {} characters",
+            MAX_QUERY_LEN
+        )));
+    }
+    let mut query = SearchQuery::default();
+    let mut text = Vec::new();
+    for token in tokenize(q) {
+        let Some((key, value)) = token.split_once(':') else {
+            text.push(token);
+            continue;
+        };
+        let unquote = |v: &str| v.trim_matches('"').to_string();
+        match key {
+            "from" => query.from.push(unquote(value.trim_start_matches('@'))),
+            "in" => query.chats.push(unquote(value.trim_start_matches('#'))),
+            "has" => match unquote(value).as_str() {
+                "file" => query.has_file = true,
+                "link" => query.has_link = true,
+                "pin" => query.has_pin = true,
+                _ => {
+                    return Err(AppError::SearchError(format!(
+                        "unknown filter has:{}",
+                        value
+                    )))
+                }
+            },
+            "before" => query.before = Some(parse_date(&unquote(value))?),
+            "after" => query.after = Some(parse_date(&unquote(value))?),
+            //not a filter, e.g. a time like 10:30
+            _ => text.push(token),
+        }
+    }
+    if query.from.iter().chain(&query.chats).any(|v| v.is_empty()) {
+        return Err(AppError::SearchError("empty filter value".to_string()));
+    }
+    let text = text.join(" ");
+    if !text
+        .trim_matches(|c: char| c == '"' || c.is_whitespace())
+        .is_empty()
+    {
+        query.text = text;
+    }
+    if query == SearchQuery::default() {
+        return Err(AppError::SearchError("empty search query".to_string()));
+    }
+    Ok(query)
+}
+
+//whitespace separated tokens, whitespace inside double quotes doesn't split
+fn tokenize(q: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in q.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, AppError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| AppError::SearchError(format!("invalid date {}, expect YYYY-MM-DD", value)))
+}
+
+//"<sort key>_<message id>"
+fn parse_cursor(cursor: &str) -> Result<(f64, i64), AppError> {
+    cursor
+        .split_once('_')
+        .and_then(|(key, id)| Some((key.parse().ok()?, id.parse().ok()?)))
+        .ok_or_else(|| AppError::SearchError(format!("invalid cursor {}", cursor)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateMessage;
+    use anyhow::Result;
+
+    fn message(content: &str) -> CreateMessage {
+        CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_search_query_should_split_filters() -> Result<()> {
+        let query = parse_search_query(
+            r#"from:@alice from:@"Kevin Yang" in:#ops has:file before:2026-01-01 "exact phrase" deploy 10:30"#,
+        )?;
+        assert_eq!(
+            query,
+            SearchQuery {
+                text: r#""exact phrase" deploy 10:30"#.to_string(),
+                from: vec!["alice".to_string(), "Kevin Yang".to_string()],
+                chats: vec!["ops".to_string()],
+                has_file: true,
+                before: NaiveDate::from_ymd_opt(2026, 1, 1),
+                ..Default::default()
+            }
+        );
+        assert!(parse_search_query("has:photo").is_err());
+        assert!(parse_search_query("after:yesterday").is_err());
+        assert!(parse_search_query(r#" "" "#).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_messages_should_respect_membership() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin3 = state
+            .find_user_by_email("kevin3.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let alice = state.find_user_by_email("alice@acme.org").await?.unwrap();
+
+        //kevin3 is in general(2) and group(5), not in private(3)
+        state
+            .create_message(message("deploying the new gateway & router"), 2, 1)
+            .await?;
+        state
+            .create_message(message("gateway deploy is done"), 2, 2)
+            .await?;
+        state
+            .create_message(message("secret gateway deploy"), 3, 1)
+            .await?;
+        state
+            .create_message(message("deploy notes for the group"), 5, 2)
+            .await?;
+
+        let search = |q: &str| SearchMessages {
+            q: q.to_string(),
+            ..Default::default()
+        };
+        let ret = state.search_messages(search("deploy"), &kevin3).await?;
+        assert_eq!(ret.hits.len(), 3);
+        let ret = state
+            .search_messages(search("deploy from:@kevin2 in:#general"), &kevin3)
+            .await?;
+        assert_eq!(ret.hits.len(), 1);
+        assert_eq!(ret.hits[0].message.sender_id, 2);
+        let ret = state
+            .search_messages(search(r#""new gateway""#), &kevin3)
+            .await?;
+        assert_eq!(ret.hits.len(), 1);
+        assert!(ret.hits[0]
+            .snippet
+            .contains("<mark>new</mark> <mark>gateway</mark> &amp;"));
+        let ret = state.search_messages(search("deploy"), &alice).await?;
+        assert!(ret.hits.is_empty());
+
+        //walk the pages by date
+        let mut input = SearchMessages {
+            order: Some(SearchOrder::Date),
+            page_size: Some(2),
+            ..search("deploy")
+        };
+        let first = state.search_messages(input.clone(), &kevin3).await?;
+        assert_eq!(first.hits.len(), 2);
+        assert!(first.hits[0].message.id > first.hits[1].message.id);
+        input.cursor = first.next_cursor;
+        let second = state.search_messages(input, &kevin3).await?;
+        assert_eq!(second.hits.len(), 1);
+        assert!(second.hits[0].message.id < first.hits[1].message.id);
+        assert!(second.next_cursor.is_none());
+        Ok(())
+    }
+}
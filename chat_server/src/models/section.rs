@@ -0,0 +1,270 @@
+use core_lib::{ChatSidebarPrefs, SidebarSection};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{AppError, AppState};
+
+const MAX_SECTION_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct CreateSidebarSection {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct ReorderSidebarSections {
+    //all section ids of the user in the new order
+    pub ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateChatSidebar {
+    #[serde(default)]
+    pub starred: bool,
+    //move the chat into a section, back to the default list if absent
+    pub section_id: Option<i64>,
+}
+
+impl AppState {
+    pub async fn list_sidebar_sections(
+        &self,
+        user_id: u64,
+    ) -> Result<Vec<SidebarSection>, AppError> {
+        let sections = sqlx::query_as(
+            r#"
+            SELECT id,user_id,name,position,created_at
+            FROM sidebar_sections
+            WHERE user_id=$1
+            ORDER BY position, id
+            "#,
+        )
+        .bind(user_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(sections)
+    }
+
+    //new section is appended to the end
+    pub async fn create_sidebar_section(
+        &self,
+        input: CreateSidebarSection,
+        user_id: u64,
+    ) -> Result<SidebarSection, AppError> {
+        let name = verify_section_name(&input.name)?;
+        let section = sqlx::query_as(
+            r#"
+            INSERT INTO sidebar_sections(user_id,name,position)
+            SELECT $1,$2,coalesce(max(position)+1,0)
+            FROM sidebar_sections
+            WHERE user_id=$1
+            RETURNING id,user_id,name,position,created_at
+            "#,
+        )
+        .bind(user_id as i64)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(map_section_name_conflict)?;
+        Ok(section)
+    }
+
+    pub async fn rename_sidebar_section(
+        &self,
+        input: CreateSidebarSection,
+        id: u64,
+        user_id: u64,
+    ) -> Result<SidebarSection, AppError> {
+        let name = verify_section_name(&input.name)?;
+        let section: Option<SidebarSection> = sqlx::query_as(
+            r#"
+            UPDATE sidebar_sections
+            SET name=$1
+            WHERE id=$2 and user_id=$3
+            RETURNING id,user_id,name,position,created_at
+            "#,
+        )
+        .bind(name)
+        .bind(id as i64)
+        .bind(user_id as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(map_section_name_conflict)?;
+        section.ok_or_else(|| AppError::NotFound(format!("sidebar section {}", id)))
+    }
+
+    //chats in the section go back to the default list
+    pub async fn delete_sidebar_section(&self, id: u64, user_id: u64) -> Result<(), AppError> {
+        let ret = sqlx::query(
+            r#"
+            DELETE FROM sidebar_sections
+            WHERE id=$1 and user_id=$2
+            "#,
+        )
+        .bind(id as i64)
+        .bind(user_id as i64)
+        .execute(&self.pool)
+        .await?;
+        if ret.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("sidebar section {}", id)));
+        }
+        Ok(())
+    }
+
+    pub async fn reorder_sidebar_sections(
+        &self,
+        input: ReorderSidebarSections,
+        user_id: u64,
+    ) -> Result<Vec<SidebarSection>, AppError> {
+        let mut current: Vec<i64> = self
+            .list_sidebar_sections(user_id)
+            .await?
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        let mut ids = input.ids.clone();
+        current.sort_unstable();
+        ids.sort_unstable();
+        if current != ids {
+            return Err(AppError::SidebarSectionError(
+                "ids must contain every section exactly once".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for (position, id) in input.ids.iter().enumerate() {
+            sqlx::query(
+                r#"
+                UPDATE sidebar_sections
+                SET position=$1
+                WHERE id=$2 and user_id=$3 and position<>$1
+                "#,
+            )
+            .bind(position as i32)
+            .bind(id)
+            .bind(user_id as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.list_sidebar_sections(user_id).await
+    }
+
+    pub async fn update_chat_sidebar(
+        &self,
+        input: UpdateChatSidebar,
+        chat_id: u64,
+        user_id: u64,
+    ) -> Result<ChatSidebarPrefs, AppError> {
+        // section must belong to the user
+        let prefs = sqlx::query_as(
+            r#"
+            UPDATE chat_members
+            SET starred=$1,section_id=$2
+            WHERE chat_id=$3 and user_id=$4
+                and ($2::BIGINT IS NULL OR EXISTS (SELECT 1 FROM sidebar_sections WHERE id=$2 and user_id=$4))
+            RETURNING chat_id,user_id,starred,section_id
+            "#,
+        )
+        .bind(input.starred)
+        .bind(input.section_id)
+        .bind(chat_id as i64)
+        .bind(user_id as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+        match (prefs, input.section_id) {
+            (Some(prefs), _) => Ok(prefs),
+            (None, Some(id)) if self.is_chat_member(chat_id as _, user_id as _).await? => {
+                Err(AppError::NotFound(format!("sidebar section {}", id)))
+            }
+            _ => Err(AppError::NotFound(format!(
+                "member {} of chat {}",
+                user_id, chat_id
+            ))),
+        }
+    }
+}
+
+fn verify_section_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_SECTION_NAME_LEN {
+        return Err(AppError::SidebarSectionError(format!(
+            "name must be 1 to {} characters",
+            MAX_SECTION_NAME_LEN
+        )));
+    }
+    Ok(name)
+}
+
+fn map_section_name_conflict(e: sqlx::Error) -> AppError {
+    match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            AppError::SidebarSectionError("section with the same name already exists".to_string())
+        }
+        e => e.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ListSidebar;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_sidebar_sections_should_keep_order() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let mut ids = vec![];
+        for name in ["work", "friends", "later"] {
+            let input = CreateSidebarSection {
+                name: name.to_string(),
+            };
+            ids.push(state.create_sidebar_section(input, 1).await?.id);
+        }
+        let input = CreateSidebarSection {
+            name: " work ".to_string(),
+        };
+        let ret = state.create_sidebar_section(input, 1).await;
+        assert!(matches!(ret, Err(AppError::SidebarSectionError(_))));
+
+        ids.reverse();
+        let input = ReorderSidebarSections { ids: ids.clone() };
+        let sections = state.reorder_sidebar_sections(input, 1).await?;
+        let names: Vec<_> = sections.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["later", "friends", "work"]);
+
+        let input = ReorderSidebarSections { ids: vec![ids[0]] };
+        let ret = state.reorder_sidebar_sections(input, 1).await;
+        assert!(matches!(ret, Err(AppError::SidebarSectionError(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_chat_sidebar_should_work() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let input = CreateSidebarSection {
+            name: "work".to_string(),
+        };
+        let section = state.create_sidebar_section(input, 1).await?;
+        let input = UpdateChatSidebar {
+            starred: true,
+            section_id: Some(section.id),
+        };
+        let prefs = state.update_chat_sidebar(input.clone(), 2, 1).await?;
+        assert!(prefs.starred);
+        assert_eq!(prefs.section_id, Some(section.id));
+
+        //section of another user
+        let ret = state.update_chat_sidebar(input, 2, 2).await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        let items = state.fetch_sidebar(1, 1, ListSidebar::default()).await?;
+        let item = items.iter().find(|i| i.chat.id == 2).unwrap();
+        assert!(item.starred);
+
+        state.delete_sidebar_section(section.id as _, 1).await?;
+        let items = state.fetch_sidebar(1, 1, ListSidebar::default()).await?;
+        let item = items.iter().find(|i| i.chat.id == 2).unwrap();
+        assert_eq!(item.section_id, None);
+        Ok(())
+    }
+}
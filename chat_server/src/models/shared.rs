@@ -0,0 +1,371 @@
+use chrono::{DateTime, Utc};
+use core_lib::{Chat, ChatType, User};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{AppError, AppState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, ToSchema, Deserialize, Serialize)]
+#[sqlx(type_name = "share_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ShareStatus {
+    Pending,
+    Active,
+}
+
+//a channel of the host workspace(chats.ws_id) shared with a guest workspace
+#[derive(Debug, Clone, FromRow, ToSchema, Deserialize, Serialize)]
+pub struct SharedChannel {
+    pub chat_id: i64,
+    pub ws_id: i64,
+    pub status: ShareStatus,
+    pub invited_by: i64,
+    pub accepted_by: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct ShareChat {
+    pub chat_id: i64,
+    //guest workspace
+    pub ws_id: i64,
+}
+
+#[derive(Debug, Clone, Default, ToSchema, Deserialize, Serialize)]
+pub struct UpdateSharedMembers {
+    //users of the caller's workspace taking part in the chat
+    #[serde(default)]
+    pub members: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, IntoParams, Deserialize, Serialize)]
+pub struct DisconnectSharedChannel {
+    //guest workspace to disconnect, required for the host, defaults to the caller's own workspace
+    pub ws_id: Option<i64>,
+}
+
+impl AppState {
+    //only admins of the host workspace can share its channels
+    pub async fn share_chat(
+        &self,
+        input: ShareChat,
+        user: &User,
+    ) -> Result<SharedChannel, AppError> {
+        self.verify_workspace_admin(user).await?;
+        let chat = match self.get_chat_by_id(input.chat_id, user.ws_id as _).await? {
+            Some(chat) if chat.ws_id == user.ws_id => chat,
+            _ => return Err(AppError::NotFound(input.chat_id.to_string())),
+        };
+        if !matches!(
+            chat.r#type,
+            ChatType::PublicChannel | ChatType::PrivateChannel
+        ) {
+            return Err(AppError::SharedChannelError(
+                "only channels can be shared".to_string(),
+            ));
+        }
+        if chat.archived_at.is_some() {
+            return Err(AppError::ChatArchived(chat.id.to_string()));
+        }
+        if input.ws_id == user.ws_id || self.find_workspace_by_id(input.ws_id as _).await?.is_none()
+        {
+            return Err(AppError::WorkSpaceNotExists(input.ws_id.to_string()));
+        }
+
+        let share = sqlx::query_as(
+            r#"
+            INSERT INTO shared_channels(chat_id,ws_id,invited_by)
+            VALUES($1,$2,$3)
+            RETURNING chat_id,ws_id,status,invited_by,accepted_by,created_at,accepted_at
+            "#,
+        )
+        .bind(chat.id)
+        .bind(input.ws_id)
+        .bind(user.id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => AppError::SharedChannelError(
+                "chat is already shared with this workspace".to_string(),
+            ),
+            e => e.into(),
+        })?;
+        Ok(share)
+    }
+
+    //shares hosted by the workspace and invitations to it
+    pub async fn list_shared_channels(&self, ws_id: u64) -> Result<Vec<SharedChannel>, AppError> {
+        let shares = sqlx::query_as(
+            r#"
+            SELECT s.chat_id,s.ws_id,s.status,s.invited_by,s.accepted_by,s.created_at,s.accepted_at
+            FROM shared_channels s
+            JOIN chats c ON c.id=s.chat_id
+            WHERE s.ws_id=$1 OR c.ws_id=$1
+            ORDER BY s.created_at DESC
+            "#,
+        )
+        .bind(ws_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(shares)
+    }
+
+    //guest workspace admin accepts the invitation and picks the users joining
+    pub async fn accept_shared_channel(
+        &self,
+        chat_id: u64,
+        input: UpdateSharedMembers,
+        user: &User,
+    ) -> Result<Chat, AppError> {
+        self.verify_workspace_admin(user).await?;
+        self.verify_workspace_members(&input.members, user.ws_id as _)
+            .await?;
+
+        let mut tx = self.pool.begin().await?;
+        let ret = sqlx::query(
+            r#"
+            UPDATE shared_channels
+            SET status='active',accepted_by=$3,accepted_at=now()
+            WHERE chat_id=$1 and ws_id=$2 and status='pending'
+            "#,
+        )
+        .bind(chat_id as i64)
+        .bind(user.ws_id)
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+        if ret.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "pending shared channel {}",
+                chat_id
+            )));
+        }
+        let chat =
+            replace_workspace_members(&mut tx, chat_id as _, user.ws_id, &input.members).await?;
+        tx.commit().await?;
+        Ok(chat)
+    }
+
+    //each side controls which of its own users take part in the chat
+    pub async fn update_shared_members(
+        &self,
+        chat_id: u64,
+        input: UpdateSharedMembers,
+        user: &User,
+    ) -> Result<Chat, AppError> {
+        self.verify_workspace_admin(user).await?;
+        self.verify_chat_writable(chat_id as _, user.ws_id as _)
+            .await?;
+        self.verify_workspace_members(&input.members, user.ws_id as _)
+            .await?;
+        let shared: bool = sqlx::query_scalar(
+            r#"SELECT EXISTS(SELECT 1 FROM shared_channels WHERE chat_id=$1 and status='active')"#,
+        )
+        .bind(chat_id as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        if !shared {
+            return Err(AppError::SharedChannelError(
+                "chat is not shared".to_string(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let chat =
+            replace_workspace_members(&mut tx, chat_id as _, user.ws_id, &input.members).await?;
+        tx.commit().await?;
+        Ok(chat)
+    }
+
+    //either side can disconnect, users of the guest workspace leave the chat
+    pub async fn disconnect_shared_channel(
+        &self,
+        chat_id: u64,
+        input: DisconnectSharedChannel,
+        user: &User,
+    ) -> Result<(), AppError> {
+        self.verify_workspace_admin(user).await?;
+        let host_ws_id: Option<i64> = sqlx::query_scalar(r#"SELECT ws_id FROM chats WHERE id=$1"#)
+            .bind(chat_id as i64)
+            .fetch_optional(&self.pool)
+            .await?;
+        let ws_id = match (host_ws_id, input.ws_id) {
+            (Some(host), Some(ws_id)) if host == user.ws_id => ws_id,
+            (Some(host), None) if host == user.ws_id => {
+                return Err(AppError::SharedChannelError(
+                    "ws_id of the guest workspace is required".to_string(),
+                ))
+            }
+            (Some(_), Some(ws_id)) if ws_id != user.ws_id => {
+                return Err(AppError::PermissionDenied(
+                    "guest workspace can only disconnect itself".to_string(),
+                ))
+            }
+            (Some(_), _) => user.ws_id,
+            (None, _) => return Err(AppError::NotFound(chat_id.to_string())),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        let status: Option<ShareStatus> = sqlx::query_scalar(
+            r#"SELECT status FROM shared_channels WHERE chat_id=$1 and ws_id=$2 FOR UPDATE"#,
+        )
+        .bind(chat_id as i64)
+        .bind(ws_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        match status {
+            Some(ShareStatus::Active) => {
+                replace_workspace_members(&mut tx, chat_id as _, ws_id, &[]).await?;
+            }
+            Some(ShareStatus::Pending) => {}
+            None => {
+                return Err(AppError::NotFound(format!(
+                    "shared channel {} with workspace {}",
+                    chat_id, ws_id
+                )))
+            }
+        }
+        sqlx::query(r#"DELETE FROM shared_channels WHERE chat_id=$1 and ws_id=$2"#)
+            .bind(chat_id as i64)
+            .bind(ws_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    //chat is hosted by the workspace or shared with it
+    pub async fn is_chat_in_workspace(&self, chat: &Chat, ws_id: i64) -> Result<bool, AppError> {
+        if chat.ws_id == ws_id {
+            return Ok(true);
+        }
+        let ret = sqlx::query_scalar(r#"SELECT chat_in_workspace($1,$2,$3)"#)
+            .bind(chat.id)
+            .bind(chat.ws_id)
+            .bind(ws_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(ret)
+    }
+
+    async fn verify_workspace_admin(&self, user: &User) -> Result<(), AppError> {
+        if !self
+            .is_workspace_admin(user.ws_id as _, user.id as _)
+            .await?
+        {
+            return Err(AppError::PermissionDenied(
+                "only workspace admin can manage shared channels".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn verify_workspace_members(&self, members: &[i64], ws_id: u64) -> Result<(), AppError> {
+        let mut ids = members.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        let users = self.fetch_chat_user_by_ids(&ids, ws_id).await?;
+        if users.len() != members.len() {
+            return Err(AppError::SharedChannelError(
+                "Some members do not exist in this workspace".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+//replace users of the workspace in the chat, other members keep their order
+async fn replace_workspace_members(
+    conn: &mut PgConnection,
+    chat_id: i64,
+    ws_id: i64,
+    members: &[i64],
+) -> Result<Chat, AppError> {
+    let chat: Option<Chat> = sqlx::query_as(
+        r#"
+        UPDATE chats c
+        SET members=ARRAY(
+            SELECT t.m
+            FROM unnest(c.members) WITH ORDINALITY AS t(m,i)
+            WHERE t.m NOT IN (SELECT id FROM users WHERE ws_id=$2)
+            ORDER BY t.i
+        ) || $3::BIGINT[]
+        WHERE c.id=$1 and chat_in_workspace(c.id,c.ws_id,$2)
+        RETURNING id,ws_id,name,type,members,created_at,archived_at,description,topic,icon,member_count
+        "#,
+    )
+    .bind(chat_id)
+    .bind(ws_id)
+    .bind(members)
+    .fetch_optional(conn)
+    .await?;
+    chat.ok_or_else(|| AppError::NotFound(chat_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateMessage;
+    use anyhow::Result;
+
+    async fn user(state: &AppState, email: &str) -> Result<User> {
+        Ok(state.find_user_by_email(email).await?.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_shared_channel_should_work() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        //make alice the admin of workspace2
+        sqlx::query(r#"UPDATE workspaces SET owner_id=4 WHERE id=2"#)
+            .execute(&state.pool)
+            .await?;
+        let kevin = user(&state, "kevin.yang.xgz@gmail.com").await?;
+        let kevin2 = user(&state, "kevin2.yang.xgz@gmail.com").await?;
+        let alice = user(&state, "alice@acme.org").await?;
+
+        let input = ShareChat {
+            chat_id: 2,
+            ws_id: 2,
+        };
+        let ret = state.share_chat(input.clone(), &kevin2).await;
+        assert!(matches!(ret, Err(AppError::PermissionDenied(_))));
+        let share = state.share_chat(input.clone(), &kevin).await?;
+        assert_eq!(share.status, ShareStatus::Pending);
+        assert!(state.share_chat(input, &kevin).await.is_err());
+        //not visible before accepted
+        assert!(state.get_chat_by_id(2, 2).await?.is_none());
+
+        let input = UpdateSharedMembers {
+            members: vec![4, 5],
+        };
+        let chat = state.accept_shared_channel(2, input, &alice).await?;
+        assert_eq!(chat.members, vec![1, 2, 3, 4, 5]);
+        assert!(state.is_chat_member(2, 4).await?);
+        assert_eq!(state.list_shared_channels(1).await?.len(), 1);
+
+        //host can't touch guest members and the other way around
+        let input = crate::models::CreateChat::new("general", &[1, 2, 3], true);
+        assert!(state.update_chat(input, 2, 1, 1).await.is_err());
+        let input = crate::models::CreateChat::new("general", &[1, 2, 4, 5], true);
+        assert!(state.update_chat(input, 2, 1, 1).await.is_ok());
+        let input = UpdateSharedMembers { members: vec![1] };
+        assert!(state.update_shared_members(2, input, &alice).await.is_err());
+
+        let input = CreateMessage {
+            content: "hello from acme".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        state.create_message(input, 2, 4).await?;
+
+        state
+            .disconnect_shared_channel(2, DisconnectSharedChannel::default(), &alice)
+            .await?;
+        let chat = state.get_chat_by_id(2, 1).await?.unwrap();
+        assert_eq!(chat.members, vec![1, 2]);
+        assert!(!state.is_chat_member(2, 4).await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::{DateTime, Utc};
+use core_lib::{Chat, NotifyLevel};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{AppError, AppState};
+
+const DEFAULT_SIDEBAR_PAGE_SIZE: u64 = 50;
+const MAX_SIDEBAR_PAGE_SIZE: u64 = 200;
+//max characters of the last message shown in the sidebar
+const SNIPPET_LEN: i32 = 100;
+
+#[derive(Debug, Clone, Default, IntoParams, Deserialize, Serialize)]
+pub struct ListSidebar {
+    //cursor, last_activity_at and id of the last item of previous page
+    pub before: Option<DateTime<Utc>>,
+    pub before_id: Option<i64>,
+    pub page_size: Option<u64>,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Debug, Clone, ToSchema, Deserialize, Serialize)]
+pub struct LastMessage {
+    pub id: i64,
+    pub sender_id: i64,
+    pub sender_name: String,
+    pub snippet: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, ToSchema, Deserialize, Serialize)]
+pub struct SidebarItem {
+    #[serde(flatten)]
+    pub chat: Chat,
+    pub last_message: Option<LastMessage>,
+    pub last_activity_at: DateTime<Utc>,
+    pub unread_count: i64,
+    pub starred: bool,
+    pub section_id: Option<i64>,
+    pub notify_level: NotifyLevel,
+    pub muted_until: Option<DateTime<Utc>>,
+    //no notification for now, by notify_level or muted_until
+    pub muted: bool,
+}
+
+#[derive(Debug, FromRow)]
+struct SidebarRow {
+    #[sqlx(flatten)]
+    chat: Chat,
+    last_activity_at: DateTime<Utc>,
+    last_message_id: Option<i64>,
+    last_sender_id: Option<i64>,
+    last_sender_name: Option<String>,
+    last_snippet: Option<String>,
+    last_message_at: Option<DateTime<Utc>>,
+    unread_count: i64,
+    starred: bool,
+    section_id: Option<i64>,
+    notify_level: NotifyLevel,
+    muted_until: Option<DateTime<Utc>>,
+    muted: bool,
+}
+
+impl AppState {
+    //chats of the user ordered by last activity, paginated by (last_activity_at, id)
+    pub async fn fetch_sidebar(
+        &self,
+        ws_id: u64,
+        user_id: u64,
+        input: ListSidebar,
+    ) -> Result<Vec<SidebarItem>, AppError> {
+        let page_size = input
+            .page_size
+            .unwrap_or(DEFAULT_SIDEBAR_PAGE_SIZE)
+            .clamp(1, MAX_SIDEBAR_PAGE_SIZE);
+        let rows: Vec<SidebarRow> = sqlx::query_as(
+            r#"
+            SELECT c.id,c.ws_id,c.name,c.type,c.members,c.created_at,c.archived_at,c.description,c.topic,c.icon,c.member_count,
+                c.last_activity_at,
+                m.id AS last_message_id,
+                m.sender_id AS last_sender_id,
+                u.fullname AS last_sender_name,
+                left(m.content,$5) AS last_snippet,
+                m.created_at AS last_message_at,
+                (
+                    SELECT count(*)
+                    FROM messages um
+                    WHERE um.chat_id=c.id and um.id > cm.last_read_id and um.sender_id <> cm.user_id
+                        and um.parent_id IS NULL and um.deleted_at IS NULL
+                        and NOT EXISTS(SELECT 1 FROM user_blocks b WHERE b.blocker_id=cm.user_id and b.blocked_id=um.sender_id)
+                ) AS unread_count,
+                cm.starred,
+                cm.section_id,
+                cm.notify_level,
+                cm.muted_until,
+                (cm.notify_level='nothing' OR coalesce(cm.muted_until > now(),false)) AS muted
+            FROM chat_members cm
+            JOIN chats c ON c.id=cm.chat_id
+            LEFT JOIN LATERAL (
+                SELECT lm.id,lm.sender_id,lm.content,lm.created_at
+                FROM messages lm
+                WHERE lm.chat_id=c.id and lm.parent_id IS NULL and lm.deleted_at IS NULL
+                    and lm.created_at >= history_start(c.id,cm.user_id)
+                    and NOT EXISTS(SELECT 1 FROM user_blocks b WHERE b.blocker_id=cm.user_id and b.blocked_id=lm.sender_id)
+                ORDER BY lm.created_at DESC, lm.id DESC
+                LIMIT 1
+            ) m ON true
+            LEFT JOIN users u ON u.id=m.sender_id
+            WHERE cm.user_id=$1 and chat_in_workspace(c.id,c.ws_id,$7) and (c.archived_at IS NOT NULL)=$2
+                and ($3::timestamptz IS NULL OR (c.last_activity_at,c.id) < ($3,$4))
+            ORDER BY c.last_activity_at DESC, c.id DESC
+            LIMIT $6
+            "#,
+        )
+        .bind(user_id as i64)
+        .bind(input.archived)
+        .bind(input.before)
+        .bind(input.before_id.unwrap_or(i64::MAX))
+        .bind(SNIPPET_LEN)
+        .bind(page_size as i64)
+        .bind(ws_id as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(SidebarItem::from).collect())
+    }
+}
+
+impl From<SidebarRow> for SidebarItem {
+    fn from(row: SidebarRow) -> Self {
+        let last_message = match (row.last_message_id, row.last_sender_id, row.last_message_at) {
+            (Some(id), Some(sender_id), Some(created_at)) => Some(LastMessage {
+                id,
+                sender_id,
+                sender_name: row.last_sender_name.unwrap_or_default(),
+                snippet: row.last_snippet.unwrap_or_default(),
+                created_at,
+            }),
+            _ => None,
+        };
+        Self {
+            chat: row.chat.compact(),
+            last_message,
+            last_activity_at: row.last_activity_at,
+            unread_count: row.unread_count,
+            starred: row.starred,
+            section_id: row.section_id,
+            notify_level: row.notify_level,
+            muted_until: row.muted_until,
+            muted: row.muted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BlockUser, CreateMessage, ListChats, UpdateNotifyPrefs};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn test_fetch_sidebar_should_order_by_activity() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        for (chat_id, content) in [(3, "in private"), (2, "in general")] {
+            let input = CreateMessage {
+                content: content.to_string(),
+                files: vec![],
+                ..Default::default()
+            };
+            state.create_message(input, chat_id, 2).await?;
+        }
+        let input = UpdateNotifyPrefs {
+            notify_level: NotifyLevel::Nothing,
+            muted_until: None,
+        };
+        state.update_notify_prefs(input, 3, 1).await?;
+
+        let items = state.fetch_sidebar(1, 1, ListSidebar::default()).await?;
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0].chat.id, 2);
+        assert_eq!(items[1].chat.id, 3);
+        let last = items[0].last_message.as_ref().unwrap();
+        assert_eq!(last.snippet, "in general");
+        assert_eq!(last.sender_name, "kevin2");
+        assert_eq!(items[0].unread_count, 1);
+        assert!(!items[0].muted);
+        assert!(items[1].muted);
+
+        let input = ListSidebar {
+            before: Some(items[1].last_activity_at),
+            before_id: Some(items[1].chat.id),
+            page_size: Some(1),
+            ..Default::default()
+        };
+        let page = state.fetch_sidebar(1, 1, input).await?;
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].chat.id, items[2].chat.id);
+
+        //messages of blocked senders are neither shown nor counted
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let input = CreateMessage {
+            content: "from kevin3".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        state.create_message(input, 2, 3).await?;
+        let input = BlockUser {
+            user_id: 3,
+            reason: None,
+        };
+        state.block_user(input, &kevin).await?;
+        let items = state.fetch_sidebar(1, 1, ListSidebar::default()).await?;
+        let last = items[0].last_message.as_ref().unwrap();
+        assert_eq!(last.snippet, "in general");
+        assert_eq!(items[0].unread_count, 1);
+
+        //deleted messages are skipped too, in the sidebar and the chat list
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let input = CreateMessage {
+            content: "oops".to_string(),
+            files: vec![],
+            ..Default::default()
+        };
+        let message = state.create_message(input, 2, 2).await?;
+        state.delete_message(message.id as _, &kevin2).await?;
+        let items = state.fetch_sidebar(1, 1, ListSidebar::default()).await?;
+        let last = items[0].last_message.as_ref().unwrap();
+        assert_eq!(last.snippet, "in general");
+        assert_eq!(items[0].unread_count, 1);
+        let chats = state.fetch_all_chat(1, 1, ListChats::default()).await?;
+        let general = chats.into_iter().find(|c| c.chat.id == 2).unwrap();
+        assert_eq!(general.unread_count, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,250 @@
+use core_lib::{Message, User, MAX_REPLY_PARTICIPANTS};
+use sqlx::PgConnection;
+
+use crate::{AppError, AppState};
+
+use super::{CreateMessage, ListMessages, ListMessagesSince, MessagesSince};
+
+impl AppState {
+    pub async fn create_reply(
+        &self,
+        input: CreateMessage,
+        parent_id: u64,
+        user: &User,
+    ) -> Result<Message, AppError> {
+        let (chat_id, deleted) = self.find_thread_root(parent_id, user).await?;
+        if deleted {
+            return Err(AppError::MessageCreateError(
+                "can't reply to a deleted message".to_string(),
+            ));
+        }
+        self.insert_message(input, chat_id as _, user.id as _, Some(parent_id as _))
+            .await
+    }
+
+    pub async fn list_replies(
+        &self,
+        input: ListMessages,
+        parent_id: u64,
+        user: &User,
+    ) -> Result<Vec<Message>, AppError> {
+        let (chat_id, _) = self.find_thread_root(parent_id, user).await?;
+        self.fetch_message_page(input, chat_id, Some(parent_id as _), user)
+            .await
+    }
+
+    //replies after seq, the thread's events are repaired like the chat's
+    pub async fn list_replies_since(
+        &self,
+        input: ListMessagesSince,
+        parent_id: u64,
+        user: &User,
+    ) -> Result<MessagesSince, AppError> {
+        let (chat_id, _) = self.find_thread_root(parent_id, user).await?;
+        let last_seq: i32 = sqlx::query_scalar("SELECT reply_count FROM messages WHERE id=$1")
+            .bind(parent_id as i64)
+            .fetch_one(&self.pool)
+            .await?;
+        self.fetch_messages_since(input, chat_id, Some(parent_id as _), last_seq as _, user)
+            .await
+    }
+
+    pub async fn follow_thread(&self, parent_id: u64, user: &User) -> Result<(), AppError> {
+        self.find_thread_root(parent_id, user).await?;
+        let mut conn = self.pool.acquire().await?;
+        set_thread_following(&mut conn, parent_id as _, user.id, true).await
+    }
+
+    pub async fn unfollow_thread(&self, parent_id: u64, user: &User) -> Result<(), AppError> {
+        self.find_thread_root(parent_id, user).await?;
+        let mut conn = self.pool.acquire().await?;
+        set_thread_following(&mut conn, parent_id as _, user.id, false).await
+    }
+
+    //chat id of a root message visible to the user and whether it's deleted
+    async fn find_thread_root(&self, id: u64, user: &User) -> Result<(i64, bool), AppError> {
+        let root = sqlx::query_as(
+            r#"
+            SELECT m.chat_id,m.deleted_at IS NOT NULL
+            FROM messages m
+            JOIN chats c ON c.id=m.chat_id
+            JOIN chat_members cm ON cm.chat_id=c.id and cm.user_id=$2
+            WHERE m.id=$1 and m.parent_id IS NULL and chat_in_workspace(c.id,c.ws_id,$3)
+                and m.created_at >= history_start(c.id,$2)
+            "#,
+        )
+        .bind(id as i64)
+        .bind(user.id)
+        .bind(user.ws_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        root.ok_or_else(|| AppError::NotFound(format!("thread {}", id)))
+    }
+}
+
+//run in the transaction inserting a reply: update the thread summary of the root,
+//the root author follows unless they unfollowed, the replier follows again.
+//reply_count is bumped by the insert, it's the seq of the reply
+pub(crate) async fn join_thread(
+    conn: &mut PgConnection,
+    parent_id: i64,
+    user_id: i64,
+) -> Result<(), AppError> {
+    let root_sender: i64 = sqlx::query_scalar(
+        r#"
+        UPDATE messages
+        SET last_reply_at=now(),
+            reply_participants=(array_prepend($2,array_remove(reply_participants,$2)))[1:$3]
+        WHERE id=$1
+        RETURNING sender_id
+        "#,
+    )
+    .bind(parent_id)
+    .bind(user_id)
+    .bind(MAX_REPLY_PARTICIPANTS)
+    .fetch_one(&mut *conn)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO thread_followers(message_id,user_id)
+        VALUES($1,$2)
+        ON CONFLICT(message_id,user_id) DO NOTHING
+        "#,
+    )
+    .bind(parent_id)
+    .bind(root_sender)
+    .execute(&mut *conn)
+    .await?;
+    set_thread_following(conn, parent_id, user_id, true).await
+}
+
+async fn set_thread_following(
+    conn: &mut PgConnection,
+    parent_id: i64,
+    user_id: i64,
+    following: bool,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO thread_followers(message_id,user_id,following)
+        VALUES($1,$2,$3)
+        ON CONFLICT(message_id,user_id) DO UPDATE SET following=EXCLUDED.following,updated_at=now()
+        "#,
+    )
+    .bind(parent_id)
+    .bind(user_id)
+    .bind(following)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UpdateChatPolicy;
+    use anyhow::Result;
+    use core_lib::PostPolicy;
+
+    fn message(content: &str) -> CreateMessage {
+        CreateMessage {
+            content: content.to_string(),
+            files: vec![],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_thread_replies_should_update_root() -> Result<()> {
+        let (_tdb, state) = AppState::new_for_test().await?;
+        let kevin = state
+            .find_user_by_email("kevin.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin2 = state
+            .find_user_by_email("kevin2.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let kevin3 = state
+            .find_user_by_email("kevin3.yang.xgz@gmail.com")
+            .await?
+            .unwrap();
+        let alice = state.find_user_by_email("alice@acme.org").await?.unwrap();
+
+        //chat 2 is general with kevin(admin), kevin2 and kevin3, announcement only
+        let root = state.create_message(message("release today"), 2, 1).await?;
+        let input = UpdateChatPolicy {
+            post_policy: Some(PostPolicy::Moderators),
+            ..Default::default()
+        };
+        state.update_chat_policy(input, 2, &kevin).await?;
+        let ret = state.create_message(message("hi"), 2, 2).await;
+        assert!(matches!(ret, Err(AppError::PostNotAllowed(_))));
+
+        let first = state
+            .create_reply(message("great"), root.id as _, &kevin2)
+            .await?;
+        assert_eq!(first.parent_id, Some(root.id));
+        state
+            .create_reply(message("which version?"), root.id as _, &kevin3)
+            .await?;
+        state
+            .create_reply(message("again"), root.id as _, &kevin2)
+            .await?;
+        let ret = state
+            .create_reply(message("nested"), first.id as _, &kevin3)
+            .await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+        let ret = state
+            .create_reply(message("hi"), root.id as _, &alice)
+            .await;
+        assert!(matches!(ret, Err(AppError::NotFound(_))));
+
+        //replies stay out of the chat
+        let messages = state
+            .list_messages(ListMessages::default(), 2, &kevin3)
+            .await?;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].reply_count, 3);
+        assert_eq!(messages[0].reply_participants, vec![2, 3]);
+        assert!(messages[0].last_reply_at.is_some());
+        let replies = state
+            .list_replies(ListMessages::default(), root.id as _, &kevin)
+            .await?;
+        assert_eq!(replies.len(), 3);
+        let seqs: Vec<_> = replies.iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, vec![Some(3), Some(2), Some(1)]);
+
+        //each thread counts its own replies
+        let input = ListMessagesSince {
+            seq: 1,
+            page_size: None,
+        };
+        let ret = state
+            .list_replies_since(input.clone(), root.id as _, &kevin)
+            .await?;
+        assert_eq!(ret.last_seq, 3);
+        let seqs: Vec<_> = ret.messages.iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, vec![Some(2), Some(3)]);
+        let ret = state.list_messages_since(input, 2, &kevin).await?;
+        assert!(ret.messages.is_empty());
+
+        let followers = |following: bool| {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT user_id FROM thread_followers WHERE message_id=$1 and following=$2 ORDER BY user_id",
+            )
+            .bind(root.id)
+            .bind(following)
+            .fetch_all(&state.pool)
+        };
+        assert_eq!(followers(true).await?, vec![1, 2, 3]);
+        state.unfollow_thread(root.id as _, &kevin).await?;
+        state
+            .create_reply(message("more"), root.id as _, &kevin3)
+            .await?;
+        assert_eq!(followers(true).await?, vec![2, 3]);
+        state.follow_thread(root.id as _, &kevin).await?;
+        assert_eq!(followers(false).await?, Vec::<i64>::new());
+        Ok(())
+    }
+}
@@ -99,11 +99,17 @@ impl AppState {
             None => Ok(None),
         }
     }
-    pub async fn fetch_chat_user_by_ids(&self, ids: &[i64]) -> Result<Vec<ChatUser>, AppError> {
-        let users = sqlx::query_as(r#"SELECT id,fullname,email FROM users WHERE id=ANY($1)"#)
-            .bind(ids)
-            .fetch_all(&self.pool)
-            .await?;
+    pub async fn fetch_chat_user_by_ids(
+        &self,
+        ids: &[i64],
+        ws_id: u64,
+    ) -> Result<Vec<ChatUser>, AppError> {
+        let users =
+            sqlx::query_as(r#"SELECT id,fullname,email FROM users WHERE id=ANY($1) and ws_id=$2"#)
+                .bind(ids)
+                .bind(ws_id as i64)
+                .fetch_all(&self.pool)
+                .await?;
         Ok(users)
     }
 
@@ -160,7 +166,6 @@ impl SigninUser {
     }
 }
 #[cfg(test)]
-
 mod tests {
 
     use super::*;
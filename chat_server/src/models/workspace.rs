@@ -31,7 +31,6 @@ impl AppState {
         .await?;
         Ok(workspace)
     }
-    #[allow(unused)]
     pub async fn find_workspace_by_id(&self, id: u64) -> Result<Option<WorkSpace>, AppError> {
         let workspace = sqlx::query_as(
             r#"
@@ -46,6 +45,11 @@ impl AppState {
         Ok(workspace)
     }
 
+    pub async fn is_workspace_admin(&self, ws_id: u64, user_id: u64) -> Result<bool, AppError> {
+        let ws = self.find_workspace_by_id(ws_id).await?;
+        Ok(matches!(ws, Some(ws) if ws.owner_id == user_id as i64))
+    }
+
     pub async fn fetch_all_chat_users(&self, workspace_id: u64) -> Result<Vec<ChatUser>, AppError> {
         let users = sqlx::query_as(
             r#"
@@ -1,5 +1,9 @@
 use axum::Router;
-use core_lib::{Chat, ChatType, ChatUser, Message, User, WorkSpace};
+use core_lib::{
+    Chat, ChatPolicy, ChatSidebarPrefs, ChatType, ChatUser, HistoryVisibility, Message,
+    MessageFormat, MessagePin, MessageReaction, NotifyLevel, PostPolicy, Reaction, ReadMarker,
+    SidebarSection, User, WorkSpace,
+};
 use utoipa::{
     openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
     Modify, OpenApi,
@@ -8,7 +12,17 @@ use utoipa::{
 use crate::AppState;
 use crate::{
     handlers::*,
-    models::{CreateChat, CreateUser, SigninUser},
+    models::{
+        AddGroupToChat, AddReaction, AuditAction, BlockReport, BlockUser, BlockedUser,
+        ChatAuditLog, ChatInvite, ChatListItem, ChatMember, ChatRole, ChatTopic, CreateChat,
+        CreateChatInvite, CreateDirectMessage, CreateMessage, CreateSidebarSection, CreateUser,
+        CreateUserGroup, CreateWorkspaceEmoji, LastMessage, MarkChatRead, MemberRole,
+        MessageRevision, MessagesSince, NotifyPrefs, PinnedMessage, ReorderSidebarSections,
+        SearchHit, SearchOrder, SearchResult, ShareChat, ShareStatus, SharedChannel, SidebarItem,
+        SigninUser, UpdateChatDescription, UpdateChatIcon, UpdateChatPolicy, UpdateChatSidebar,
+        UpdateChatTopic, UpdateMemberRole, UpdateMessage, UpdateNotifyPrefs, UpdateSharedMembers,
+        UpdateUserGroup, UpdateUserGroupMembers, UserGroup, WorkspaceEmoji,
+    },
     ErrorOutput,
 };
 use utoipa_rapidoc::RapiDoc;
@@ -25,11 +39,72 @@ pub(crate) trait OpenApiRouter {
         signup_handler,
         signin_handler,
         list_chat_handler,
+        list_sidebar_handler,
         create_chat_handler,
+        get_or_create_dm_handler,
         get_chat_handler,
+        update_chat_topic_handler,
+        list_chat_topics_handler,
+        update_chat_description_handler,
+        update_chat_icon_handler,
+        get_chat_policy_handler,
+        update_chat_policy_handler,
+        archive_chat_handler,
+        unarchive_chat_handler,
+        delete_chat_handler,
+        get_notify_prefs_handler,
+        update_notify_prefs_handler,
+        mark_chat_read_handler,
+        update_chat_sidebar_handler,
+        list_sidebar_sections_handler,
+        create_sidebar_section_handler,
+        rename_sidebar_section_handler,
+        delete_sidebar_section_handler,
+        reorder_sidebar_sections_handler,
+        list_shared_channels_handler,
+        share_chat_handler,
+        accept_shared_channel_handler,
+        update_shared_members_handler,
+        disconnect_shared_channel_handler,
+        create_chat_invite_handler,
+        list_chat_invites_handler,
+        revoke_chat_invite_handler,
+        redeem_chat_invite_handler,
+        update_member_role_handler,
+        list_chat_members_handler,
+        list_blocked_users_handler,
+        block_user_handler,
+        unblock_user_handler,
+        list_block_reports_handler,
+        list_user_groups_handler,
+        create_user_group_handler,
+        get_user_group_handler,
+        update_user_group_handler,
+        delete_user_group_handler,
+        update_user_group_members_handler,
+        add_group_to_chat_handler,
+        list_chat_audit_logs_handler,
         list_messages_handler,
+        list_messages_since_handler,
+        update_message_handler,
+        delete_message_handler,
+        list_message_revisions_handler,
+        list_replies_handler,
+        list_replies_since_handler,
+        create_reply_handler,
+        follow_thread_handler,
+        unfollow_thread_handler,
+        add_reaction_handler,
+        remove_reaction_handler,
+        list_workspace_emojis_handler,
+        create_workspace_emoji_handler,
+        delete_workspace_emoji_handler,
+        list_pinned_messages_handler,
+        pin_message_handler,
+        unpin_message_handler,
+        search_messages_handler,
     ),
-        components(schemas( User,Chat,ChatType,ChatUser,Message,WorkSpace,SigninUser,CreateUser,CreateChat,AuthOutput,ErrorOutput)),
+        components(schemas( User,Chat,ChatType,ChatUser,Message,MessageFormat,MessagesSince,CreateMessage,UpdateMessage,MessageRevision,Reaction,MessageReaction,AddReaction,MessagePin,PinnedMessage,SearchOrder,SearchHit,SearchResult,WorkspaceEmoji,CreateWorkspaceEmoji,WorkSpace,SigninUser,CreateUser,CreateChat,CreateDirectMessage,ChatTopic,UpdateChatTopic,UpdateChatDescription,UpdateChatIcon,ChatPolicy,PostPolicy,HistoryVisibility,UpdateChatPolicy,NotifyLevel,NotifyPrefs,UpdateNotifyPrefs,ChatListItem,SidebarItem,LastMessage,MarkChatRead,ReadMarker,SidebarSection,ChatSidebarPrefs,CreateSidebarSection,ReorderSidebarSections,UpdateChatSidebar,SharedChannel,ShareStatus,ShareChat,UpdateSharedMembers,ChatInvite,CreateChatInvite,ChatRole,MemberRole,ChatMember,UpdateMemberRole,BlockUser,BlockedUser,BlockReport,UserGroup,CreateUserGroup,UpdateUserGroup,UpdateUserGroupMembers,AddGroupToChat,AuditAction,ChatAuditLog,AuthOutput,ErrorOutput)),
         modifiers(&SecurityAddon),
         tags(
             (name = "todo", description = "Todo items management API")
@@ -68,7 +68,7 @@ impl ChatServer {
     async fn signin(&self) -> Result<String> {
         let res = self
             .client
-            .post(&format!("http://{}/api/signin", self.addr))
+            .post(format!("http://{}/api/signin", self.addr))
             .header("content-type", "application/json")
             .body(r#"{"email":"kevin.yang.xgz@gmail.com","password":"test123456"}"#)
             .send()
@@ -82,7 +82,7 @@ impl ChatServer {
     async fn create_chat(&self) -> Result<Chat> {
         let res = self
             .client
-            .post(&format!("http://{}/api/chats", self.addr))
+            .post(format!("http://{}/api/chats", self.addr))
             .header("Authorization", format!("Bearer {}", self.token))
             .header("Content-Type", "application/json")
             .body(
@@ -110,7 +110,7 @@ impl ChatServer {
         let form = Form::new().part("file", files);
         let res: reqwest::Response = self
             .client
-            .post(&format!("http://{}/api/upload", self.addr))
+            .post(format!("http://{}/api/upload", self.addr))
             .header("Authorization", format!("Bearer {}", self.token))
             .multipart(form)
             .send()
@@ -125,7 +125,7 @@ impl ChatServer {
 
         let res = self
             .client
-            .post(&format!("http://{}/api/chats/{}", self.addr, chat.id))
+            .post(format!("http://{}/api/chats/{}", self.addr, chat.id))
             .header("Authorization", format!("Bearer {}", self.token))
             .header("Content-Type", "application/json")
             .body(body)
@@ -151,7 +151,7 @@ impl NotifyServer {
                 .unwrap();
         });
 
-        let mut es = EventSource::get(&format!("http://{}/events?access_token={}", addr, token));
+        let mut es = EventSource::get(format!("http://{}/events?access_token={}", addr, token));
         tokio::spawn(async move {
             while let Some(event) = es.next().await {
                 match event {
@@ -0,0 +1,178 @@
+use std::net::SocketAddr;
+
+use anyhow::Result;
+
+use core_lib::{Chat, ChatUser};
+use reqwest::{
+    multipart::{Form, Part},
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+const WILD_ADDR: &str = "0.0.0.0:0";
+const KEVIN: &str = "kevin.yang.xgz@gmail.com";
+const ALICE: &str = "alice@acme.org";
+
+#[derive(Debug, Deserialize)]
+struct AuthToken {
+    token: String,
+}
+
+//users of workspace1 and workspace2 talking to the same server
+struct TenantClient {
+    addr: SocketAddr,
+    token: String,
+    client: reqwest::Client,
+}
+
+#[tokio::test]
+async fn cross_workspace_membership_should_be_rejected() -> Result<()> {
+    let (_tdb, state) = chat_server::AppState::new_for_test().await?;
+    let addr = start_server(state).await?;
+    let kevin = TenantClient::signin(addr, KEVIN).await?;
+
+    //alice(4) is in workspace2
+    let body = json!({"name": "mixed", "members": [1, 4], "public": false});
+    let res = kevin.post("/api/chats", &body).await?;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = kevin
+        .post("/api/chats/dm", &json!({"members": [4]}))
+        .await?;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let body = json!({"name": "general", "members": [1, 2, 3, 4], "public": true});
+    let res = kevin.patch("/api/chats/2", &body).await?;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = kevin.get("/api/chats/2").await?;
+    let chat: Chat = res.json().await?;
+    assert_eq!(chat.members, vec![1, 2, 3]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cross_workspace_access_should_be_rejected() -> Result<()> {
+    let (_tdb, state) = chat_server::AppState::new_for_test().await?;
+    let addr = start_server(state).await?;
+    let kevin = TenantClient::signin(addr, KEVIN).await?;
+    let alice = TenantClient::signin(addr, ALICE).await?;
+
+    //chat 2 belongs to workspace1, chat 6 to workspace2
+    for path in ["/api/chats/2", "/api/chats/2/messages?page_size=10"] {
+        let res = alice.get(path).await?;
+        assert!(
+            res.status().is_client_error(),
+            "{} should be rejected",
+            path
+        );
+    }
+    let res = kevin.get("/api/chats/6").await?;
+    assert!(res.status().is_client_error());
+    let res = alice
+        .post("/api/chats/2", &json!({"content": "hi", "files": []}))
+        .await?;
+    assert!(res.status().is_client_error());
+
+    let chats: Vec<Chat> = alice.get("/api/chats").await?.json().await?;
+    assert!(chats.iter().all(|c| c.ws_id == 2));
+    let chats: Vec<Value> = alice.get("/api/chats/sidebar").await?.json().await?;
+    assert_eq!(chats.len(), 1);
+    assert_eq!(chats[0]["id"], 6);
+    let users: Vec<ChatUser> = alice.get("/api/users").await?.json().await?;
+    let mut ids: Vec<_> = users.iter().map(|u| u.id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![4, 5]);
+
+    //files of workspace2 can't be read or attached from workspace1
+    let url = alice.upload().await?;
+    let res = kevin.get(&format!("/api{}", url)).await?;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    let res = kevin
+        .post("/api/chats/2", &json!({"content": "hi", "files": [url]}))
+        .await?;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+async fn start_server(state: chat_server::AppState) -> Result<SocketAddr> {
+    let app = chat_server::get_router(state).await?;
+    let listener = TcpListener::bind(WILD_ADDR).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service())
+            .await
+            .unwrap();
+    });
+    Ok(addr)
+}
+
+impl TenantClient {
+    async fn signin(addr: SocketAddr, email: &str) -> Result<Self> {
+        let client = reqwest::Client::new();
+        let res = client
+            .post(format!("http://{}/api/signin", addr))
+            .json(&json!({"email": email, "password": "test123456"}))
+            .send()
+            .await?;
+        assert_eq!(res.status(), 200);
+        let ret = res.json::<AuthToken>().await?;
+        Ok(Self {
+            addr,
+            token: ret.token,
+            client,
+        })
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        let res = self
+            .client
+            .get(format!("http://{}{}", self.addr, path))
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+        Ok(res)
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> Result<reqwest::Response> {
+        let res = self
+            .client
+            .post(format!("http://{}{}", self.addr, path))
+            .bearer_auth(&self.token)
+            .json(body)
+            .send()
+            .await?;
+        Ok(res)
+    }
+
+    async fn patch(&self, path: &str, body: &Value) -> Result<reqwest::Response> {
+        let res = self
+            .client
+            .patch(format!("http://{}{}", self.addr, path))
+            .bearer_auth(&self.token)
+            .json(body)
+            .send()
+            .await?;
+        Ok(res)
+    }
+
+    async fn upload(&self) -> Result<String> {
+        let file_data = include_bytes!("../Cargo.toml");
+        let files = Part::bytes(file_data)
+            .file_name("Cargo.toml")
+            .mime_str("text/plain")?;
+        let form = Form::new().part("file", files);
+        let res = self
+            .client
+            .post(format!("http://{}/api/upload", self.addr))
+            .bearer_auth(&self.token)
+            .multipart(form)
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let mut ret: Vec<String> = res.json().await?;
+        Ok(ret.remove(0))
+    }
+}
@@ -24,6 +24,24 @@ values (
         '$argon2id$v=19$m=19456,t=2,p=1$uA3da3UQnoSVOFSwF4Aw3Q$9BF+ZDpP+cvERAjYnESkRFQ5GJU5OCb+0GQe3twXzqg',
         1
     );
+-- users of workspace2
+insert into users (fullname, email, password_hash, ws_id)
+values (
+        'alice',
+        'alice@acme.org',
+        '$argon2id$v=19$m=19456,t=2,p=1$uA3da3UQnoSVOFSwF4Aw3Q$9BF+ZDpP+cvERAjYnESkRFQ5GJU5OCb+0GQe3twXzqg',
+        2
+    ),
+    (
+        'bob',
+        'bob@acme.org',
+        '$argon2id$v=19$m=19456,t=2,p=1$uA3da3UQnoSVOFSwF4Aw3Q$9BF+ZDpP+cvERAjYnESkRFQ5GJU5OCb+0GQe3twXzqg',
+        2
+    );
+-- kevin is the admin of workspace1
+update workspaces
+set owner_id = 1
+where id = 1;
 -- insert chats
 INSERT INTO chats(ws_id, name, type, members)
 VALUES(1, 'general', 'public_channel', '{ 1, 2, 3 }'),
@@ -32,3 +50,6 @@ VALUES(1, 'general', 'public_channel', '{ 1, 2, 3 }'),
 INSERT INTO chats(ws_id, type, members)
 VALUES(1, 'single', '{ 1, 2 }'),
     (1, 'group', '{ 1, 2, 3 }');
+-- insert chats of workspace2
+INSERT INTO chats(ws_id, name, type, members)
+VALUES(2, 'acme', 'private_channel', '{ 4, 5 }');
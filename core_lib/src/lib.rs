@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
+mod markdown;
 mod middlewares;
 mod utils;
+pub use markdown::render_markdown;
 pub use middlewares::*;
 pub use utils::{DecodingKey, EncodingKey};
 
@@ -40,8 +42,29 @@ pub struct Chat {
     pub ws_id: i64,
     pub name: Option<String>,
     pub r#type: ChatType,
+    //empty in lists and events of chats with more than MAX_INLINE_MEMBERS members
     pub members: Vec<i64>,
     pub created_at: DateTime<Utc>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    pub topic: Option<String>,
+    pub icon: Option<String>,
+    pub member_count: i32,
+}
+
+//larger chats only ship member_count, members are read from the members endpoint
+pub const MAX_INLINE_MEMBERS: i32 = 100;
+
+//repliers shown on the root message of a thread
+pub const MAX_REPLY_PARTICIPANTS: i32 = 3;
+
+impl Chat {
+    pub fn compact(mut self) -> Self {
+        if self.member_count > MAX_INLINE_MEMBERS {
+            self.members = vec![];
+        }
+        self
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, sqlx::Type, PartialOrd, ToSchema)]
@@ -54,6 +77,83 @@ pub enum ChatType {
     PublicChannel,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct ReadMarker {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub last_read_id: i64,
+}
+
+//custom section in the sidebar of a user
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct SidebarSection {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub position: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+//sidebar state of a chat for a member
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct ChatSidebarPrefs {
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub starred: bool,
+    pub section_id: Option<i64>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, sqlx::Type, ToSchema,
+)]
+#[sqlx(type_name = "post_policy", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PostPolicy {
+    #[default]
+    Everyone,
+    //moderators, workspace admins and designated posters
+    Moderators,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, sqlx::Type, ToSchema,
+)]
+#[sqlx(type_name = "history_visibility", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryVisibility {
+    #[default]
+    Full,
+    //messages sent after the member joined
+    SinceJoin,
+    //messages of the last history_days days
+    LastDays,
+}
+
+//who can post, pin and read the history of a chat, sent when it changes
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct ChatPolicy {
+    pub chat_id: i64,
+    pub post_policy: PostPolicy,
+    //minimal seconds between two messages of a member, 0 means off
+    pub slow_mode_secs: i32,
+    pub history_visibility: HistoryVisibility,
+    pub history_days: Option<i32>,
+    //who can pin and unpin messages
+    pub pin_policy: PostPolicy,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, sqlx::Type, ToSchema,
+)]
+#[sqlx(type_name = "notify_level", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyLevel {
+    #[default]
+    All,
+    Mentions,
+    Nothing,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
 pub struct Message {
     pub id: i64,
@@ -62,6 +162,104 @@ pub struct Message {
     pub content: String,
     pub files: Vec<String>,
     pub created_at: DateTime<Utc>,
+    //set when the author edited the message
+    pub edited_at: Option<DateTime<Utc>>,
+    //tombstone of a deleted message, content and files are scrubbed
+    pub deleted_at: Option<DateTime<Utc>>,
+    //root message of the thread this message replies to
+    pub parent_id: Option<i64>,
+    //thread summary, only set on root messages
+    #[serde(default)]
+    pub reply_count: i32,
+    //most recent repliers first, at most MAX_REPLY_PARTICIPANTS
+    #[serde(default)]
+    pub reply_participants: Vec<i64>,
+    pub last_reply_at: Option<DateTime<Utc>>,
+    //mentions parsed from content, in order of appearance
+    #[sqlx(json)]
+    #[serde(default)]
+    pub mentions: Vec<MentionRange>,
+    //gap-free position in the chat, or in the thread for replies
+    pub seq: Option<i64>,
+    #[serde(default)]
+    pub format: MessageFormat,
+    //sanitized rendering of markdown content, not stored
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub html: Option<String>,
+    //only filled when listing messages
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq, sqlx::Type, ToSchema,
+)]
+#[sqlx(type_name = "message_format", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    #[default]
+    Plain,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash, sqlx::Type, ToSchema)]
+#[sqlx(type_name = "mention_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MentionKind {
+    User,
+    //@handle of a user group
+    Group,
+    //members online when the message was sent
+    Here,
+    Channel,
+}
+
+//a mention token in the content of a message
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct MentionRange {
+    //offsets in characters of content, end excluded
+    pub start: i32,
+    pub end: i32,
+    pub kind: MentionKind,
+    //mentioned user or group
+    pub target_id: Option<i64>,
+}
+
+//reactions of a message with the same emoji, in order of the first reaction
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct Reaction {
+    pub emoji: String,
+    pub count: i64,
+    pub user_ids: Vec<i64>,
+}
+
+//a single reaction, sent when added or removed
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct MessageReaction {
+    pub message_id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub emoji: String,
+    pub created_at: DateTime<Utc>,
+}
+
+//a pinned message of a chat, sent when pinned or unpinned
+#[derive(Debug, Clone, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct MessagePin {
+    pub message_id: i64,
+    pub chat_id: i64,
+    pub pinned_by: i64,
+    pub pinned_at: DateTime<Utc>,
+}
+
+impl Message {
+    //fill html of a markdown message, tombstones have nothing to render
+    pub fn render(&mut self) {
+        self.html = (self.format == MessageFormat::Markdown && self.deleted_at.is_none())
+            .then(|| render_markdown(&self.content));
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,96 @@
+use pulldown_cmark::{html, Event, Parser, Tag, TagEnd};
+
+//links to anything else are rendered as their text
+const LINK_SCHEMES: [&str; 3] = ["http://", "https://", "mailto:"];
+
+//render the supported subset: paragraphs, code blocks and inline code, links,
+//lists and quotes. other syntax is kept as text and raw html is escaped, so the
+//output only has the tags produced here
+pub fn render_markdown(src: &str) -> String {
+    //whether the end tag of each open link or image is kept
+    let mut links: Vec<bool> = Vec::new();
+    let events = Parser::new(src).filter_map(|event| match event {
+        Event::Start(tag) => match tag {
+            Tag::Paragraph | Tag::CodeBlock(_) | Tag::List(_) | Tag::Item | Tag::BlockQuote(_) => {
+                Some(Event::Start(tag))
+            }
+            Tag::Heading { .. } | Tag::HtmlBlock => Some(Event::Start(Tag::Paragraph)),
+            //images become links to the image, links can't be nested
+            Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }
+            | Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            } => {
+                let keep = is_safe_url(&dest_url) && !links.contains(&true);
+                links.push(keep);
+                keep.then_some(Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }))
+            }
+            _ => None,
+        },
+        Event::End(tag) => match tag {
+            TagEnd::Paragraph
+            | TagEnd::CodeBlock
+            | TagEnd::List(_)
+            | TagEnd::Item
+            | TagEnd::BlockQuote(_) => Some(Event::End(tag)),
+            TagEnd::Heading(_) | TagEnd::HtmlBlock => Some(Event::End(TagEnd::Paragraph)),
+            TagEnd::Link | TagEnd::Image => links
+                .pop()
+                .unwrap_or_default()
+                .then_some(Event::End(TagEnd::Link)),
+            _ => None,
+        },
+        Event::Text(_) | Event::Code(_) | Event::SoftBreak | Event::HardBreak => Some(event),
+        Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+        _ => None,
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    LINK_SCHEMES.iter().any(|scheme| url.starts_with(scheme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_markdown_should_keep_subset() {
+        let src = "# Release\n\nsee [notes](https://example.com/notes \"v2\") and `cargo run`\n\n\
+            - one\n- **two**\n\n> quoted\n\n```rust\nfn main() {}\n```\n";
+        assert_eq!(
+            render_markdown(src),
+            "<p>Release</p>\n\
+            <p>see <a href=\"https://example.com/notes\" title=\"v2\">notes</a> and <code>cargo run</code></p>\n\
+            <ul>\n<li>one</li>\n<li>two</li>\n</ul>\n\
+            <blockquote>\n<p>quoted</p>\n</blockquote>\n\
+            <pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn render_markdown_should_escape_html_and_unsafe_links() {
+        let src = "<script>alert(1)</script>\n\n[click](javascript:alert(1)) ![x](http://a.b/x.png) <b>hi</b>";
+        assert_eq!(
+            render_markdown(src),
+            "<p>&lt;script&gt;alert(1)&lt;/script&gt;\n</p>\n\
+            <p>click <a href=\"http://a.b/x.png\">x</a> &lt;b&gt;hi&lt;/b&gt;</p>\n"
+        );
+    }
+}
@@ -0,0 +1,7 @@
+-- Add migration script here
+-- archived chats are read-only and hidden from default listings
+ALTER TABLE chats
+ADD COLUMN archived_at timestamptz;
+CREATE INDEX IF NOT EXISTS chats_ws_id_archived_at_index ON chats(ws_id, archived_at);
+-- files referenced by messages, used to find unreferenced files after purge
+CREATE INDEX IF NOT EXISTS messages_files_index ON messages USING GIN(files);
@@ -0,0 +1,15 @@
+-- Add migration script here
+-- chat description, topic and icon
+ALTER TABLE chats
+ADD COLUMN description VARCHAR(1024),
+    ADD COLUMN topic VARCHAR(256),
+    ADD COLUMN icon VARCHAR(256);
+-- topic audit trail
+CREATE TABLE IF NOT EXISTS chat_topics (
+    id BIGSERIAL PRIMARY KEY,
+    chat_id BIGINT NOT NULL references chats(id) ON DELETE CASCADE,
+    topic VARCHAR(256),
+    changed_by BIGINT NOT NULL references users(id),
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS chat_topics_chat_id_index ON chat_topics(chat_id, created_at DESC);
@@ -0,0 +1,42 @@
+-- Add migration script here
+-- single and group chats without name are direct messages, keyed by their sorted member set
+CREATE OR REPLACE FUNCTION chat_dm_key(chat_type, BIGINT []) RETURNS TEXT AS $$
+SELECT CASE
+        WHEN $1 IN ('single', 'group') THEN array_to_string(
+            ARRAY(
+                SELECT DISTINCT m
+                FROM unnest($2) m
+                ORDER BY m
+            ),
+            ','
+        )
+    END $$ LANGUAGE sql IMMUTABLE;
+ALTER TABLE chats
+ADD COLUMN dm_key TEXT;
+-- keep the oldest chat for existing duplicates, the backfill is not a chat update
+ALTER TABLE chats DISABLE TRIGGER add_to_chat_trigger;
+UPDATE chats c
+SET dm_key = k.dm_key
+FROM (
+        SELECT DISTINCT ON (ws_id, chat_dm_key(type, members)) id,
+            chat_dm_key(type, members) AS dm_key
+        FROM chats
+        WHERE type IN ('single', 'group')
+        ORDER BY ws_id,
+            chat_dm_key(type, members),
+            created_at,
+            id
+    ) k
+WHERE c.id = k.id;
+ALTER TABLE chats ENABLE TRIGGER add_to_chat_trigger;
+CREATE UNIQUE INDEX IF NOT EXISTS chats_ws_id_dm_key_index ON chats(ws_id, dm_key)
+WHERE dm_key IS NOT NULL;
+CREATE OR REPLACE FUNCTION set_chat_dm_key() RETURNS TRIGGER AS $$ BEGIN NEW.dm_key := chat_dm_key(NEW.type, NEW.members);
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER set_chat_dm_key_trigger BEFORE
+INSERT
+    OR
+UPDATE OF type,
+    members ON chats FOR EACH ROW EXECUTE FUNCTION set_chat_dm_key();
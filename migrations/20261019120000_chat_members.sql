@@ -0,0 +1,71 @@
+-- Add migration script here
+-- per member state of a chat, kept in sync with chats.members
+create type notify_level as enum('all', 'mentions', 'nothing');
+CREATE TABLE IF NOT EXISTS chat_members (
+    chat_id BIGINT NOT NULL references chats(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL references users(id),
+    joined_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+    notify_level notify_level NOT NULL DEFAULT 'all',
+    muted_until timestamptz,
+    PRIMARY KEY (chat_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS chat_members_user_id_index ON chat_members(user_id);
+INSERT INTO chat_members(chat_id, user_id, joined_at)
+SELECT id,
+    unnest(members),
+    created_at
+FROM chats ON CONFLICT DO NOTHING;
+CREATE OR REPLACE FUNCTION sync_chat_members() RETURNS TRIGGER AS $$ BEGIN IF TG_OP = 'UPDATE' THEN
+DELETE FROM chat_members
+WHERE chat_id = NEW.id
+    AND NOT (user_id = ANY(NEW.members));
+END IF;
+INSERT INTO chat_members(chat_id, user_id)
+SELECT NEW.id,
+    unnest(NEW.members) ON CONFLICT DO NOTHING;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER sync_chat_members_trigger
+AFTER
+INSERT
+    OR
+UPDATE OF members ON chats FOR EACH ROW EXECUTE FUNCTION sync_chat_members();
+-- notify with the members whose notification preference is not the default
+CREATE OR REPLACE FUNCTION add_to_message()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  MENTIONS bigint[];
+  PREFS json;
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    RAISE NOTICE 'add_to_message: %', NEW;
+    -- select chat with chat_id in NEW
+    SELECT
+      members INTO CHAT_MEMBERS
+    FROM
+      chats
+    WHERE
+      id = NEW.chat_id;
+    -- members mentioned by @fullname
+    SELECT
+      coalesce(array_agg(id), '{}') INTO MENTIONS
+    FROM
+      users
+    WHERE
+      id = ANY(CHAT_MEMBERS) AND position('@' || fullname IN NEW.content) > 0;
+    SELECT
+      coalesce(json_agg(json_build_object('user_id', user_id, 'notify_level', notify_level, 'muted_until', muted_until)), '[]') INTO PREFS
+    FROM
+      chat_members
+    WHERE
+      chat_id = NEW.chat_id AND (notify_level <> 'all' OR muted_until > now());
+    PERFORM
+      pg_notify('chat_message_created', json_build_object('message', NEW, 'members', CHAT_MEMBERS, 'mentions', MENTIONS, 'prefs', PREFS)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,54 @@
+-- Add migration script here
+-- last message read by each member
+ALTER TABLE chat_members
+ADD COLUMN last_read_id BIGINT NOT NULL DEFAULT 0;
+UPDATE chat_members cm
+SET last_read_id = coalesce(
+        (
+            SELECT max(id)
+            FROM messages
+            WHERE chat_id = cm.chat_id
+        ),
+        0
+    );
+CREATE INDEX IF NOT EXISTS messages_chat_id_id_index ON messages(chat_id, id DESC);
+-- new members start with the history already read
+CREATE OR REPLACE FUNCTION sync_chat_members() RETURNS TRIGGER AS $$ BEGIN IF TG_OP = 'UPDATE' THEN
+DELETE FROM chat_members
+WHERE chat_id = NEW.id
+    AND NOT (user_id = ANY(NEW.members));
+END IF;
+INSERT INTO chat_members(chat_id, user_id, last_read_id)
+SELECT NEW.id,
+    unnest(NEW.members),
+    coalesce(
+        (
+            SELECT max(id)
+            FROM messages
+            WHERE chat_id = NEW.id
+        ),
+        0
+    ) ON CONFLICT DO NOTHING;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+-- sync read marker to the other devices of the member
+CREATE OR REPLACE FUNCTION chat_member_read() RETURNS TRIGGER AS $$ BEGIN IF NEW.last_read_id IS DISTINCT
+FROM OLD.last_read_id THEN PERFORM pg_notify(
+        'chat_read_updated',
+        json_build_object(
+            'chat_id',
+            NEW.chat_id,
+            'user_id',
+            NEW.user_id,
+            'last_read_id',
+            NEW.last_read_id
+        )::text
+    );
+END IF;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER chat_member_read_trigger
+AFTER
+UPDATE OF last_read_id ON chat_members FOR EACH ROW EXECUTE FUNCTION chat_member_read();
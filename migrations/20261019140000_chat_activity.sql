@@ -0,0 +1,85 @@
+-- Add migration script here
+-- last message of chat, used to sort chats by activity
+ALTER TABLE chats
+ADD COLUMN last_message_id BIGINT,
+    ADD COLUMN last_activity_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP;
+-- the backfill is not a chat update
+ALTER TABLE chats DISABLE TRIGGER add_to_chat_trigger;
+UPDATE chats c
+SET last_message_id = m.id,
+    last_activity_at = m.created_at
+FROM (
+        SELECT DISTINCT ON (chat_id) chat_id,
+            id,
+            created_at
+        FROM messages
+        ORDER BY chat_id,
+            id DESC
+    ) m
+WHERE c.id = m.chat_id;
+UPDATE chats
+SET last_activity_at = created_at
+WHERE last_message_id IS NULL;
+ALTER TABLE chats ENABLE TRIGGER add_to_chat_trigger;
+CREATE INDEX IF NOT EXISTS chats_last_activity_at_index ON chats(last_activity_at DESC, id DESC);
+-- activity changes are not chat updates
+CREATE OR REPLACE FUNCTION add_to_chat() RETURNS TRIGGER AS $$ BEGIN IF TG_OP = 'UPDATE'
+    AND NEW.last_message_id IS DISTINCT
+FROM OLD.last_message_id THEN RETURN NEW;
+END IF;
+Raise Notice 'add_to_chat: %',
+NEW;
+PERFORM pg_notify(
+    'chat_updated',
+    json_build_object(
+        'op',
+        TG_OP,
+        'old',
+        OLD,
+        'new',
+        NEW
+    )::text
+);
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE OR REPLACE FUNCTION add_to_message()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  MENTIONS bigint[];
+  PREFS json;
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    RAISE NOTICE 'add_to_message: %', NEW;
+    -- bump chat activity and select chat with chat_id in NEW
+    UPDATE
+      chats
+    SET
+      last_message_id = NEW.id,
+      last_activity_at = NEW.created_at
+    WHERE
+      id = NEW.chat_id
+    RETURNING
+      members INTO CHAT_MEMBERS;
+    -- members mentioned by @fullname
+    SELECT
+      coalesce(array_agg(id), '{}') INTO MENTIONS
+    FROM
+      users
+    WHERE
+      id = ANY(CHAT_MEMBERS) AND position('@' || fullname IN NEW.content) > 0;
+    SELECT
+      coalesce(json_agg(json_build_object('user_id', user_id, 'notify_level', notify_level, 'muted_until', muted_until)), '[]') INTO PREFS
+    FROM
+      chat_members
+    WHERE
+      chat_id = NEW.chat_id AND (notify_level <> 'all' OR muted_until > now());
+    PERFORM
+      pg_notify('chat_message_created', json_build_object('message', NEW, 'members', CHAT_MEMBERS, 'mentions', MENTIONS, 'prefs', PREFS)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
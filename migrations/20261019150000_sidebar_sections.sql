@@ -0,0 +1,61 @@
+-- Add migration script here
+-- custom sidebar sections of each user
+CREATE TABLE IF NOT EXISTS sidebar_sections(
+    id bigserial PRIMARY KEY,
+    user_id bigint NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name varchar(64) NOT NULL,
+    position int NOT NULL DEFAULT 0,
+    created_at timestamptz DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT sidebar_sections_user_id_name_key UNIQUE (user_id, name)
+);
+CREATE INDEX IF NOT EXISTS sidebar_sections_user_id_position_index ON sidebar_sections(user_id, position);
+-- starred chats and chats moved into a section
+ALTER TABLE chat_members
+ADD COLUMN starred BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN section_id BIGINT REFERENCES sidebar_sections(id) ON DELETE
+SET NULL;
+-- sync sidebar to the other devices of the user
+CREATE OR REPLACE FUNCTION sidebar_section_updated() RETURNS TRIGGER AS $$ BEGIN PERFORM pg_notify(
+        'sidebar_section_updated',
+        json_build_object(
+            'op',
+            TG_OP,
+            'old',
+            OLD,
+            'new',
+            NEW
+        )::text
+    );
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER sidebar_section_updated_trigger
+AFTER
+INSERT
+    OR
+UPDATE
+    OR DELETE ON sidebar_sections FOR EACH ROW EXECUTE FUNCTION sidebar_section_updated();
+CREATE OR REPLACE FUNCTION chat_member_sidebar_updated() RETURNS TRIGGER AS $$ BEGIN IF NEW.starred IS DISTINCT
+FROM OLD.starred
+    OR NEW.section_id IS DISTINCT
+FROM OLD.section_id THEN PERFORM pg_notify(
+        'chat_sidebar_updated',
+        json_build_object(
+            'chat_id',
+            NEW.chat_id,
+            'user_id',
+            NEW.user_id,
+            'starred',
+            NEW.starred,
+            'section_id',
+            NEW.section_id
+        )::text
+    );
+END IF;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER chat_member_sidebar_updated_trigger
+AFTER
+UPDATE OF starred,
+    section_id ON chat_members FOR EACH ROW EXECUTE FUNCTION chat_member_sidebar_updated();
@@ -0,0 +1,35 @@
+-- Add migration script here
+-- members of a chat must belong to the workspace of the chat
+CREATE OR REPLACE FUNCTION verify_chat_workspace() RETURNS TRIGGER AS $$ BEGIN IF EXISTS (
+        SELECT 1
+        FROM unnest(NEW.members) AS m(id)
+            LEFT JOIN users u ON u.id = m.id
+        WHERE u.ws_id IS DISTINCT
+        FROM NEW.ws_id
+    ) THEN RAISE EXCEPTION 'chat % has members outside workspace %',
+    NEW.id,
+    NEW.ws_id USING ERRCODE = 'check_violation';
+END IF;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER verify_chat_workspace_trigger BEFORE
+INSERT
+    OR
+UPDATE OF ws_id,
+    members ON chats FOR EACH ROW EXECUTE FUNCTION verify_chat_workspace();
+-- messages can only be sent by members of the chat
+CREATE OR REPLACE FUNCTION verify_message_sender() RETURNS TRIGGER AS $$ BEGIN IF NOT EXISTS (
+        SELECT 1
+        FROM chat_members
+        WHERE chat_id = NEW.chat_id
+            AND user_id = NEW.sender_id
+    ) THEN RAISE EXCEPTION 'user % is not a member of chat %',
+    NEW.sender_id,
+    NEW.chat_id USING ERRCODE = 'check_violation';
+END IF;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER verify_message_sender_trigger BEFORE
+INSERT ON messages FOR EACH ROW EXECUTE FUNCTION verify_message_sender();
@@ -0,0 +1,39 @@
+-- Add migration script here
+-- channels shared with other workspaces, chats.ws_id stays the host workspace
+CREATE TYPE share_status AS ENUM ('pending', 'active');
+CREATE TABLE IF NOT EXISTS shared_channels(
+    chat_id BIGINT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    ws_id BIGINT NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    status share_status NOT NULL DEFAULT 'pending',
+    invited_by BIGINT NOT NULL REFERENCES users(id),
+    accepted_by BIGINT REFERENCES users(id),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    accepted_at timestamptz,
+    PRIMARY KEY (chat_id, ws_id)
+);
+CREATE INDEX IF NOT EXISTS shared_channels_ws_id_index ON shared_channels(ws_id, status);
+-- chat is visible in a workspace when it is the host or an active guest
+CREATE OR REPLACE FUNCTION chat_in_workspace(c_id BIGINT, c_ws_id BIGINT, ws BIGINT) RETURNS BOOLEAN AS $$
+SELECT c_ws_id = ws
+    OR EXISTS (
+        SELECT 1
+        FROM shared_channels
+        WHERE chat_id = c_id
+            AND ws_id = ws
+            AND status = 'active'
+    );
+$$ LANGUAGE sql STABLE;
+-- members may also come from the guest workspaces
+CREATE OR REPLACE FUNCTION verify_chat_workspace() RETURNS TRIGGER AS $$ BEGIN IF EXISTS (
+        SELECT 1
+        FROM unnest(NEW.members) AS m(id)
+            LEFT JOIN users u ON u.id = m.id
+        WHERE u.ws_id IS NULL
+            OR NOT chat_in_workspace(NEW.id, NEW.ws_id, u.ws_id)
+    ) THEN RAISE EXCEPTION 'chat % has members outside workspace %',
+    NEW.id,
+    NEW.ws_id USING ERRCODE = 'check_violation';
+END IF;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
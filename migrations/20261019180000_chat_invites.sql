@@ -0,0 +1,60 @@
+-- Add migration script here
+-- moderators manage the chat, the creator of a chat is its first moderator
+CREATE TYPE chat_role AS ENUM ('member', 'moderator');
+ALTER TABLE chat_members
+ADD COLUMN role chat_role NOT NULL DEFAULT 'member';
+ALTER TABLE chats
+ADD COLUMN created_by BIGINT REFERENCES users(id);
+CREATE OR REPLACE FUNCTION sync_chat_members() RETURNS TRIGGER AS $$ BEGIN IF TG_OP = 'UPDATE' THEN
+DELETE FROM chat_members
+WHERE chat_id = NEW.id
+    AND NOT (user_id = ANY(NEW.members));
+END IF;
+INSERT INTO chat_members(chat_id, user_id, last_read_id, role)
+SELECT NEW.id,
+    m.id,
+    coalesce(
+        (
+            SELECT max(id)
+            FROM messages
+            WHERE chat_id = NEW.id
+        ),
+        0
+    ),
+    CASE
+        WHEN m.id = NEW.created_by THEN 'moderator'::chat_role
+        ELSE 'member'::chat_role
+    END
+FROM unnest(NEW.members) AS m(id) ON CONFLICT DO NOTHING;
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+-- invite links of private channels and groups
+CREATE TABLE IF NOT EXISTS chat_invites(
+    id BIGSERIAL PRIMARY KEY,
+    chat_id BIGINT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    token VARCHAR(64) NOT NULL UNIQUE DEFAULT replace(gen_random_uuid()::text, '-', '') || replace(gen_random_uuid()::text, '-', ''),
+    created_by BIGINT NOT NULL REFERENCES users(id),
+    expires_at timestamptz NOT NULL,
+    max_uses INT,
+    uses INT NOT NULL DEFAULT 0,
+    revoked_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS chat_invites_chat_id_index ON chat_invites(chat_id, created_at DESC);
+-- audit trail of chat administration
+CREATE TYPE audit_action AS ENUM (
+    'invite_created',
+    'invite_revoked',
+    'invite_redeemed',
+    'role_changed'
+);
+CREATE TABLE IF NOT EXISTS chat_audit_logs(
+    id BIGSERIAL PRIMARY KEY,
+    chat_id BIGINT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    action audit_action NOT NULL,
+    detail JSONB NOT NULL DEFAULT '{}',
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS chat_audit_logs_chat_id_index ON chat_audit_logs(chat_id, created_at DESC);
@@ -0,0 +1,12 @@
+-- Add migration script here
+-- who may post top level messages in a chat, everyone can still read, react and reply in threads
+CREATE TYPE post_policy AS ENUM ('everyone', 'moderators');
+ALTER TABLE chats
+ADD COLUMN post_policy post_policy NOT NULL DEFAULT 'everyone',
+    ADD COLUMN slow_mode_secs INT NOT NULL DEFAULT 0 CHECK (slow_mode_secs >= 0);
+-- designated posters may post in chats restricted to moderators
+ALTER TYPE chat_role
+ADD VALUE IF NOT EXISTS 'poster' BEFORE 'moderator';
+ALTER TYPE audit_action
+ADD VALUE IF NOT EXISTS 'policy_changed';
+CREATE INDEX IF NOT EXISTS chat_id_sender_id_index ON messages(chat_id, sender_id, created_at DESC);
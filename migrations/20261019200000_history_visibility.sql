@@ -0,0 +1,22 @@
+-- Add migration script here
+-- how much of the history members of a chat can read
+CREATE TYPE history_visibility AS ENUM ('full', 'since_join', 'last_days');
+ALTER TABLE chats
+ADD COLUMN history_visibility history_visibility NOT NULL DEFAULT 'full',
+    ADD COLUMN history_days INT CHECK (history_days > 0),
+    ADD CONSTRAINT chats_last_days_check CHECK (
+        history_visibility <> 'last_days'
+        OR history_days IS NOT NULL
+    );
+-- earliest message a user can read in a chat, non members can't read anything when history starts at join
+CREATE OR REPLACE FUNCTION history_start(c_id BIGINT, u_id BIGINT) RETURNS timestamptz AS $$
+SELECT CASE
+        c.history_visibility
+        WHEN 'since_join' THEN coalesce(cm.joined_at, 'infinity'::timestamptz)
+        WHEN 'last_days' THEN now() - make_interval(days => c.history_days)
+        ELSE '-infinity'::timestamptz
+    END
+FROM chats c
+    LEFT JOIN chat_members cm ON cm.chat_id = c.id
+    AND cm.user_id = u_id
+WHERE c.id = c_id $$ LANGUAGE sql STABLE;
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- clients read the size of large chats without their member ids
+ALTER TABLE chats
+ADD COLUMN member_count INT GENERATED ALWAYS AS (cardinality(members)) STORED;
+-- maintained by notify_server, a user is online while holding an event stream
+-- on any instance. each instance counts its own streams
+CREATE TABLE IF NOT EXISTS user_presence(
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    instance_id VARCHAR(64) NOT NULL,
+    connections INT NOT NULL DEFAULT 0,
+    last_seen_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, instance_id)
+);
+CREATE INDEX IF NOT EXISTS chat_members_chat_id_user_id_index ON chat_members(chat_id, user_id);
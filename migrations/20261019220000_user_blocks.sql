@@ -0,0 +1,60 @@
+-- Add migration script here
+-- users blocked by another user of the same workspace, only visible to the blocker
+CREATE TABLE IF NOT EXISTS user_blocks(
+    blocker_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    blocked_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- shown to workspace admins without the blocker
+    reason VARCHAR(256),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (blocker_id, blocked_id),
+    CHECK (blocker_id <> blocked_id)
+);
+CREATE INDEX IF NOT EXISTS user_blocks_blocked_id_index ON user_blocks(blocked_id);
+-- members who blocked the sender don't receive the message
+CREATE OR REPLACE FUNCTION add_to_message()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  MENTIONS bigint[];
+  PREFS json;
+  BLOCKED_BY bigint[];
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    RAISE NOTICE 'add_to_message: %', NEW;
+    -- bump chat activity and select chat with chat_id in NEW
+    UPDATE
+      chats
+    SET
+      last_message_id = NEW.id,
+      last_activity_at = NEW.created_at
+    WHERE
+      id = NEW.chat_id
+    RETURNING
+      members INTO CHAT_MEMBERS;
+    -- members mentioned by @fullname
+    SELECT
+      coalesce(array_agg(id), '{}') INTO MENTIONS
+    FROM
+      users
+    WHERE
+      id = ANY(CHAT_MEMBERS) AND position('@' || fullname IN NEW.content) > 0;
+    SELECT
+      coalesce(json_agg(json_build_object('user_id', user_id, 'notify_level', notify_level, 'muted_until', muted_until)), '[]') INTO PREFS
+    FROM
+      chat_members
+    WHERE
+      chat_id = NEW.chat_id AND (notify_level <> 'all' OR muted_until > now());
+    SELECT
+      coalesce(array_agg(blocker_id), '{}') INTO BLOCKED_BY
+    FROM
+      user_blocks
+    WHERE
+      blocked_id = NEW.sender_id AND blocker_id = ANY(CHAT_MEMBERS);
+    PERFORM
+      pg_notify('chat_message_created', json_build_object('message', NEW, 'members', CHAT_MEMBERS, 'mentions', MENTIONS, 'prefs', PREFS, 'blocked_by', BLOCKED_BY)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,77 @@
+-- Add migration script here
+-- named groups of workspace users, mentioned by @handle
+CREATE TABLE IF NOT EXISTS user_groups(
+    id BIGSERIAL PRIMARY KEY,
+    ws_id BIGINT NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    handle VARCHAR(32) NOT NULL,
+    name VARCHAR(64) NOT NULL,
+    description TEXT,
+    created_by BIGINT NOT NULL REFERENCES users(id),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (ws_id, handle)
+);
+CREATE TABLE IF NOT EXISTS user_group_members(
+    group_id BIGINT NOT NULL REFERENCES user_groups(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (group_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS user_group_members_user_id_index ON user_group_members(user_id);
+-- members of mentioned groups are mentioned too
+CREATE OR REPLACE FUNCTION add_to_message()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  MENTIONS bigint[];
+  PREFS json;
+  BLOCKED_BY bigint[];
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    RAISE NOTICE 'add_to_message: %', NEW;
+    -- bump chat activity and select chat with chat_id in NEW
+    UPDATE
+      chats
+    SET
+      last_message_id = NEW.id,
+      last_activity_at = NEW.created_at
+    WHERE
+      id = NEW.chat_id
+    RETURNING
+      members INTO CHAT_MEMBERS;
+    -- members mentioned by @fullname or by the @handle of one of their groups
+    SELECT
+      coalesce(array_agg(DISTINCT id), '{}') INTO MENTIONS
+    FROM (
+      SELECT
+        id
+      FROM
+        users
+      WHERE
+        id = ANY(CHAT_MEMBERS) AND position('@' || fullname IN NEW.content) > 0
+      UNION
+      SELECT
+        gm.user_id
+      FROM
+        user_groups g
+        JOIN user_group_members gm ON gm.group_id = g.id
+      WHERE
+        gm.user_id = ANY(CHAT_MEMBERS) AND position('@' || g.handle IN NEW.content) > 0) m;
+    SELECT
+      coalesce(json_agg(json_build_object('user_id', user_id, 'notify_level', notify_level, 'muted_until', muted_until)), '[]') INTO PREFS
+    FROM
+      chat_members
+    WHERE
+      chat_id = NEW.chat_id AND (notify_level <> 'all' OR muted_until > now());
+    SELECT
+      coalesce(array_agg(blocker_id), '{}') INTO BLOCKED_BY
+    FROM
+      user_blocks
+    WHERE
+      blocked_id = NEW.sender_id AND blocker_id = ANY(CHAT_MEMBERS);
+    PERFORM
+      pg_notify('chat_message_created', json_build_object('message', NEW, 'members', CHAT_MEMBERS, 'mentions', MENTIONS, 'prefs', PREFS, 'blocked_by', BLOCKED_BY)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,46 @@
+-- Add migration script here
+-- edited messages keep their previous versions for moderators
+ALTER TABLE messages
+  ADD COLUMN IF NOT EXISTS edited_at timestamptz;
+CREATE TABLE IF NOT EXISTS message_revisions(
+    id BIGSERIAL PRIMARY KEY,
+    message_id BIGINT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    -- content before the edit
+    content TEXT NOT NULL,
+    edited_by BIGINT NOT NULL REFERENCES users(id),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS message_revisions_message_id_index ON message_revisions(message_id, id);
+-- notify chat members of edited messages
+CREATE OR REPLACE FUNCTION message_updated()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  BLOCKED_BY bigint[];
+BEGIN
+  IF TG_OP = 'UPDATE' AND NEW.edited_at IS DISTINCT FROM OLD.edited_at THEN
+    RAISE NOTICE 'message_updated: %', NEW;
+    SELECT
+      members INTO CHAT_MEMBERS
+    FROM
+      chats
+    WHERE
+      id = NEW.chat_id;
+    SELECT
+      coalesce(array_agg(blocker_id), '{}') INTO BLOCKED_BY
+    FROM
+      user_blocks
+    WHERE
+      blocked_id = NEW.sender_id AND blocker_id = ANY(CHAT_MEMBERS);
+    PERFORM
+      pg_notify('chat_message_updated', json_build_object('op', 'edit', 'message', NEW, 'members', CHAT_MEMBERS, 'blocked_by', BLOCKED_BY)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+CREATE TRIGGER message_updated_trigger
+  AFTER UPDATE ON messages
+  FOR EACH ROW
+  EXECUTE FUNCTION message_updated();
@@ -0,0 +1,45 @@
+-- Add migration script here
+-- deleted messages stay as tombstones with their content scrubbed
+ALTER TABLE messages
+  ADD COLUMN IF NOT EXISTS deleted_at timestamptz,
+  ADD COLUMN IF NOT EXISTS deleted_by BIGINT REFERENCES users(id);
+ALTER TYPE audit_action
+ADD VALUE IF NOT EXISTS 'message_deleted';
+-- notify chat members of edited and deleted messages
+CREATE OR REPLACE FUNCTION message_updated()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  BLOCKED_BY bigint[];
+  OP text;
+BEGIN
+  IF TG_OP <> 'UPDATE' THEN
+    RETURN NEW;
+  END IF;
+  IF NEW.deleted_at IS DISTINCT FROM OLD.deleted_at THEN
+    OP := 'delete';
+  ELSIF NEW.edited_at IS DISTINCT FROM OLD.edited_at THEN
+    OP := 'edit';
+  ELSE
+    RETURN NEW;
+  END IF;
+  RAISE NOTICE 'message_updated: % %', OP, NEW;
+  SELECT
+    members INTO CHAT_MEMBERS
+  FROM
+    chats
+  WHERE
+    id = NEW.chat_id;
+  SELECT
+    coalesce(array_agg(blocker_id), '{}') INTO BLOCKED_BY
+  FROM
+    user_blocks
+  WHERE
+    blocked_id = NEW.sender_id AND blocker_id = ANY(CHAT_MEMBERS);
+  PERFORM
+    pg_notify('chat_message_updated', json_build_object('op', OP, 'message', NEW, 'members', CHAT_MEMBERS, 'blocked_by', BLOCKED_BY)::text);
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
@@ -0,0 +1,94 @@
+-- Add migration script here
+-- replies to a root message form a thread, the root keeps a summary
+ALTER TABLE messages
+  ADD COLUMN IF NOT EXISTS parent_id BIGINT REFERENCES messages(id) ON DELETE CASCADE,
+  ADD COLUMN IF NOT EXISTS reply_count INT NOT NULL DEFAULT 0,
+  -- most recent repliers first
+  ADD COLUMN IF NOT EXISTS reply_participants BIGINT[] NOT NULL DEFAULT '{}',
+  ADD COLUMN IF NOT EXISTS last_reply_at timestamptz;
+CREATE INDEX IF NOT EXISTS messages_parent_id_index ON messages(parent_id, id DESC)
+WHERE
+  parent_id IS NOT NULL;
+-- unfollowed threads keep their row so replies don't follow the root author again
+CREATE TABLE IF NOT EXISTS thread_followers(
+    message_id BIGINT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    following BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_id, user_id)
+);
+-- replies don't bump the chat, only thread followers are notified
+CREATE OR REPLACE FUNCTION add_to_message()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  MENTIONS bigint[];
+  PREFS json;
+  BLOCKED_BY bigint[];
+  FOLLOWERS bigint[];
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    RAISE NOTICE 'add_to_message: %', NEW;
+    IF NEW.parent_id IS NULL THEN
+      -- bump chat activity and select chat with chat_id in NEW
+      UPDATE
+        chats
+      SET
+        last_message_id = NEW.id,
+        last_activity_at = NEW.created_at
+      WHERE
+        id = NEW.chat_id
+      RETURNING
+        members INTO CHAT_MEMBERS;
+    ELSE
+      SELECT
+        members INTO CHAT_MEMBERS
+      FROM
+        chats
+      WHERE
+        id = NEW.chat_id;
+      SELECT
+        coalesce(array_agg(user_id), '{}') INTO FOLLOWERS
+      FROM
+        thread_followers
+      WHERE
+        message_id = NEW.parent_id AND following;
+    END IF;
+    -- members mentioned by @fullname or by the @handle of one of their groups
+    SELECT
+      coalesce(array_agg(DISTINCT id), '{}') INTO MENTIONS
+    FROM (
+      SELECT
+        id
+      FROM
+        users
+      WHERE
+        id = ANY(CHAT_MEMBERS) AND position('@' || fullname IN NEW.content) > 0
+      UNION
+      SELECT
+        gm.user_id
+      FROM
+        user_groups g
+        JOIN user_group_members gm ON gm.group_id = g.id
+      WHERE
+        gm.user_id = ANY(CHAT_MEMBERS) AND position('@' || g.handle IN NEW.content) > 0) m;
+    SELECT
+      coalesce(json_agg(json_build_object('user_id', user_id, 'notify_level', notify_level, 'muted_until', muted_until)), '[]') INTO PREFS
+    FROM
+      chat_members
+    WHERE
+      chat_id = NEW.chat_id AND (notify_level <> 'all' OR muted_until > now());
+    SELECT
+      coalesce(array_agg(blocker_id), '{}') INTO BLOCKED_BY
+    FROM
+      user_blocks
+    WHERE
+      blocked_id = NEW.sender_id AND blocker_id = ANY(CHAT_MEMBERS);
+    PERFORM
+      pg_notify('chat_message_created', json_build_object('message', NEW, 'members', CHAT_MEMBERS, 'mentions', MENTIONS, 'prefs', PREFS, 'blocked_by', BLOCKED_BY, 'followers', FOLLOWERS)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
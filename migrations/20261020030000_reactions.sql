@@ -0,0 +1,58 @@
+-- Add migration script here
+-- custom emoji of a workspace, used in reactions as :name:
+CREATE TABLE IF NOT EXISTS workspace_emojis(
+    id BIGSERIAL PRIMARY KEY,
+    ws_id BIGINT NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(32) NOT NULL,
+    -- uploaded image file url
+    url VARCHAR(256) NOT NULL,
+    created_by BIGINT NOT NULL REFERENCES users(id),
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (ws_id, name)
+);
+CREATE TABLE IF NOT EXISTS message_reactions(
+    message_id BIGINT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- unicode emoji or :name: of a custom emoji
+    emoji VARCHAR(64) NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_id, emoji, user_id)
+);
+-- notify chat members of added and removed reactions
+CREATE OR REPLACE FUNCTION message_reaction_updated()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  REACTION message_reactions;
+  CHAT_ID bigint;
+  CHAT_MEMBERS bigint[];
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    REACTION := NEW;
+  ELSE
+    REACTION := OLD;
+  END IF;
+  RAISE NOTICE 'message_reaction_updated: % %', TG_OP, REACTION;
+  SELECT
+    c.id,
+    c.members INTO CHAT_ID,
+    CHAT_MEMBERS
+  FROM
+    messages m
+    JOIN chats c ON c.id = m.chat_id
+  WHERE
+    m.id = REACTION.message_id;
+  -- the message is gone together with its reactions
+  IF CHAT_ID IS NULL THEN
+    RETURN NULL;
+  END IF;
+  PERFORM
+    pg_notify('message_reaction_updated', json_build_object('op', TG_OP, 'reaction', json_build_object('message_id', REACTION.message_id, 'chat_id', CHAT_ID, 'user_id', REACTION.user_id, 'emoji', REACTION.emoji, 'created_at', REACTION.created_at), 'members', CHAT_MEMBERS)::text);
+  RETURN NULL;
+END;
+$$
+LANGUAGE plpgsql;
+CREATE TRIGGER message_reaction_updated_trigger
+  AFTER INSERT OR DELETE ON message_reactions
+  FOR EACH ROW
+  EXECUTE FUNCTION message_reaction_updated();
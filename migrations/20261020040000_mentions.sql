@@ -0,0 +1,87 @@
+-- Add migration script here
+-- mentions are parsed by the server, ranges are kept on the message and the
+-- mentioned members in a side table
+CREATE TYPE mention_kind AS ENUM (
+  'user',
+  'group',
+  'here',
+  'channel'
+);
+ALTER TABLE messages
+  ADD COLUMN IF NOT EXISTS mentions JSONB NOT NULL DEFAULT '[]';
+CREATE TABLE IF NOT EXISTS message_mentions(
+    message_id BIGINT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- most direct way the member was mentioned
+    kind mention_kind NOT NULL,
+    PRIMARY KEY (message_id, user_id)
+);
+CREATE INDEX IF NOT EXISTS message_mentions_user_id_index ON message_mentions(user_id, message_id DESC);
+-- mentioned members are stored after the message, notify when the transaction commits
+CREATE OR REPLACE FUNCTION add_to_message()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHAT_MEMBERS bigint[];
+  MENTIONS bigint[];
+  PREFS json;
+  BLOCKED_BY bigint[];
+  FOLLOWERS bigint[];
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    RAISE NOTICE 'add_to_message: %', NEW;
+    IF NEW.parent_id IS NULL THEN
+      -- bump chat activity and select chat with chat_id in NEW
+      UPDATE
+        chats
+      SET
+        last_message_id = NEW.id,
+        last_activity_at = NEW.created_at
+      WHERE
+        id = NEW.chat_id
+      RETURNING
+        members INTO CHAT_MEMBERS;
+    ELSE
+      SELECT
+        members INTO CHAT_MEMBERS
+      FROM
+        chats
+      WHERE
+        id = NEW.chat_id;
+      SELECT
+        coalesce(array_agg(user_id), '{}') INTO FOLLOWERS
+      FROM
+        thread_followers
+      WHERE
+        message_id = NEW.parent_id AND following;
+    END IF;
+    SELECT
+      coalesce(array_agg(user_id), '{}') INTO MENTIONS
+    FROM
+      message_mentions
+    WHERE
+      message_id = NEW.id;
+    SELECT
+      coalesce(json_agg(json_build_object('user_id', user_id, 'notify_level', notify_level, 'muted_until', muted_until)), '[]') INTO PREFS
+    FROM
+      chat_members
+    WHERE
+      chat_id = NEW.chat_id AND (notify_level <> 'all' OR muted_until > now());
+    SELECT
+      coalesce(array_agg(blocker_id), '{}') INTO BLOCKED_BY
+    FROM
+      user_blocks
+    WHERE
+      blocked_id = NEW.sender_id AND blocker_id = ANY(CHAT_MEMBERS);
+    PERFORM
+      pg_notify('chat_message_created', json_build_object('message', NEW, 'members', CHAT_MEMBERS, 'mentions', MENTIONS, 'prefs', PREFS, 'blocked_by', BLOCKED_BY, 'followers', FOLLOWERS)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+DROP TRIGGER IF EXISTS add_to_message_trigger ON messages;
+CREATE CONSTRAINT TRIGGER add_to_message_trigger
+  AFTER INSERT ON messages DEFERRABLE INITIALLY DEFERRED
+  FOR EACH ROW
+  EXECUTE FUNCTION add_to_message();
@@ -0,0 +1,49 @@
+-- Add migration script here
+-- who can pin and unpin messages, same roles as post_policy
+ALTER TABLE chats
+ADD COLUMN pin_policy post_policy NOT NULL DEFAULT 'everyone';
+CREATE TABLE IF NOT EXISTS pinned_messages(
+    message_id BIGINT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    chat_id BIGINT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    pinned_by BIGINT NOT NULL REFERENCES users(id),
+    pinned_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS pinned_messages_chat_id_idx ON pinned_messages(chat_id, pinned_at DESC);
+ALTER TYPE audit_action
+ADD VALUE IF NOT EXISTS 'message_pinned';
+ALTER TYPE audit_action
+ADD VALUE IF NOT EXISTS 'message_unpinned';
+-- notify chat members of pinned and unpinned messages
+CREATE OR REPLACE FUNCTION message_pin_updated()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  PIN pinned_messages;
+  CHAT_MEMBERS bigint[];
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    PIN := NEW;
+  ELSE
+    PIN := OLD;
+  END IF;
+  RAISE NOTICE 'message_pin_updated: % %', TG_OP, PIN;
+  SELECT
+    members INTO CHAT_MEMBERS
+  FROM
+    chats
+  WHERE
+    id = PIN.chat_id;
+  -- the chat is gone together with its pins
+  IF CHAT_MEMBERS IS NULL THEN
+    RETURN NULL;
+  END IF;
+  PERFORM
+    pg_notify('message_pin_updated', json_build_object('op', TG_OP, 'pin', row_to_json(PIN), 'members', CHAT_MEMBERS)::text);
+  RETURN NULL;
+END;
+$$
+LANGUAGE plpgsql;
+CREATE TRIGGER message_pin_updated_trigger
+  AFTER INSERT OR DELETE ON pinned_messages
+  FOR EACH ROW
+  EXECUTE FUNCTION message_pin_updated();
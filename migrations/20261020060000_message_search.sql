@@ -0,0 +1,4 @@
+-- Add migration script here
+-- full-text search over message content. the expression is indexed instead of
+-- stored, a tsvector column would end up in the notify payloads of messages
+CREATE INDEX IF NOT EXISTS messages_content_search_idx ON messages USING GIN(to_tsvector('english', content));
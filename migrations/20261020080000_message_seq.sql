@@ -0,0 +1,75 @@
+-- Add migration script here
+-- gap-free sequence number of the messages of a chat, replies take one in their
+-- thread instead, the root's reply_count is the last seq of the thread
+ALTER TABLE chats
+ADD COLUMN IF NOT EXISTS last_seq BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE messages
+ADD COLUMN IF NOT EXISTS seq BIGINT;
+UPDATE messages m
+SET seq = s.seq
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY chat_id, parent_id ORDER BY created_at, id) AS seq
+    FROM messages
+) s
+WHERE m.id = s.id;
+-- the backfill is not a chat update
+ALTER TABLE chats DISABLE TRIGGER add_to_chat_trigger;
+UPDATE chats c
+SET last_seq = coalesce((SELECT max(seq) FROM messages WHERE chat_id = c.id), 0);
+ALTER TABLE chats ENABLE TRIGGER add_to_chat_trigger;
+CREATE UNIQUE INDEX IF NOT EXISTS messages_chat_seq_idx ON messages(chat_id, seq) WHERE parent_id IS NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS messages_thread_seq_idx ON messages(parent_id, seq) WHERE parent_id IS NOT NULL;
+-- the chat or root row stays locked until the inserting transaction ends, a
+-- rolled back message gives its number back
+CREATE OR REPLACE FUNCTION assign_message_seq()
+  RETURNS TRIGGER
+  AS $$
+BEGIN
+  IF NEW.parent_id IS NULL THEN
+    UPDATE
+      chats
+    SET
+      last_seq = last_seq + 1
+    WHERE
+      id = NEW.chat_id
+    RETURNING
+      last_seq INTO NEW.seq;
+  ELSE
+    UPDATE
+      messages
+    SET
+      reply_count = reply_count + 1
+    WHERE
+      id = NEW.parent_id
+    RETURNING
+      reply_count INTO NEW.seq;
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+CREATE TRIGGER assign_message_seq_trigger
+  BEFORE INSERT ON messages
+  FOR EACH ROW
+  EXECUTE FUNCTION assign_message_seq();
+-- activity and sequence changes are not chat updates
+CREATE OR REPLACE FUNCTION add_to_chat() RETURNS TRIGGER AS $$ BEGIN IF TG_OP = 'UPDATE'
+    AND (NEW.last_message_id IS DISTINCT FROM OLD.last_message_id
+        OR NEW.last_seq IS DISTINCT FROM OLD.last_seq) THEN RETURN NEW;
+END IF;
+Raise Notice 'add_to_chat: %',
+NEW;
+PERFORM pg_notify(
+    'chat_updated',
+    json_build_object(
+        'op',
+        TG_OP,
+        'old',
+        OLD,
+        'new',
+        NEW
+    )::text
+);
+RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
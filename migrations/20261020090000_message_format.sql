@@ -0,0 +1,5 @@
+-- Add migration script here
+-- markdown content is rendered to html when read, only the source is stored
+CREATE TYPE message_format AS ENUM ('plain', 'markdown');
+ALTER TABLE messages
+ADD COLUMN format message_format NOT NULL DEFAULT 'plain';
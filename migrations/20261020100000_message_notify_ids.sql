@@ -0,0 +1,101 @@
+-- Add migration script here
+-- pg_notify payloads are limited to 8000 bytes, member lists and preferences of
+-- large chats don't fit. message events only carry ids, notify_server looks up
+-- the message and its recipients
+CREATE OR REPLACE FUNCTION add_to_message()
+  RETURNS TRIGGER
+  AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    RAISE NOTICE 'add_to_message: %', NEW.id;
+    -- replies don't bump the chat
+    IF NEW.parent_id IS NULL THEN
+      UPDATE
+        chats
+      SET
+        last_message_id = NEW.id,
+        last_activity_at = NEW.created_at
+      WHERE
+        id = NEW.chat_id;
+    END IF;
+    PERFORM
+      pg_notify('chat_message_created', json_build_object('message_id', NEW.id)::text);
+  END IF;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+CREATE OR REPLACE FUNCTION message_updated()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  OP text;
+BEGIN
+  IF TG_OP <> 'UPDATE' THEN
+    RETURN NEW;
+  END IF;
+  IF NEW.deleted_at IS DISTINCT FROM OLD.deleted_at THEN
+    OP := 'delete';
+  ELSIF NEW.edited_at IS DISTINCT FROM OLD.edited_at THEN
+    OP := 'edit';
+  ELSE
+    RETURN NEW;
+  END IF;
+  RAISE NOTICE 'message_updated: % %', OP, NEW.id;
+  PERFORM
+    pg_notify('chat_message_updated', json_build_object('op', OP, 'message_id', NEW.id)::text);
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
+CREATE OR REPLACE FUNCTION message_reaction_updated()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  REACTION message_reactions;
+  CHAT_ID bigint;
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    REACTION := NEW;
+  ELSE
+    REACTION := OLD;
+  END IF;
+  RAISE NOTICE 'message_reaction_updated: % %', TG_OP, REACTION;
+  SELECT
+    m.chat_id INTO CHAT_ID
+  FROM
+    messages m
+  WHERE
+    m.id = REACTION.message_id;
+  -- the message is gone together with its reactions
+  IF CHAT_ID IS NULL THEN
+    RETURN NULL;
+  END IF;
+  PERFORM
+    pg_notify('message_reaction_updated', json_build_object('op', TG_OP, 'reaction', json_build_object('message_id', REACTION.message_id, 'chat_id', CHAT_ID, 'user_id', REACTION.user_id, 'emoji', REACTION.emoji, 'created_at', REACTION.created_at))::text);
+  RETURN NULL;
+END;
+$$
+LANGUAGE plpgsql;
+CREATE OR REPLACE FUNCTION message_pin_updated()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  PIN pinned_messages;
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    PIN := NEW;
+  ELSE
+    PIN := OLD;
+  END IF;
+  RAISE NOTICE 'message_pin_updated: % %', TG_OP, PIN;
+  -- the chat is gone together with its pins
+  IF NOT EXISTS (SELECT 1 FROM chats WHERE id = PIN.chat_id) THEN
+    RETURN NULL;
+  END IF;
+  PERFORM
+    pg_notify('message_pin_updated', json_build_object('op', TG_OP, 'pin', row_to_json(PIN))::text);
+  RETURN NULL;
+END;
+$$
+LANGUAGE plpgsql;
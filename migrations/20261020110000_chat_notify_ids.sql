@@ -0,0 +1,57 @@
+-- Add migration script here
+-- chat events carry ids like message events: the old and new rows of a large
+-- chat hold two member lists and overflow the 8000 bytes of pg_notify.
+-- notify_server loads the chat, the payload only names the changed columns and
+-- the removed members, who can't be looked up any more. they are sent in
+-- chunks, only the first one carries the changed columns
+CREATE OR REPLACE FUNCTION add_to_chat()
+  RETURNS TRIGGER
+  AS $$
+DECLARE
+  CHUNK CONSTANT int := 200;
+  CHAT_ID bigint;
+  FIELDS text[] := '{}';
+  REMOVED bigint[] := '{}';
+  CHAT json;
+BEGIN
+  IF TG_OP = 'UPDATE' THEN
+    -- activity and sequence changes are not chat updates
+    IF NEW.last_message_id IS DISTINCT FROM OLD.last_message_id OR NEW.last_seq IS DISTINCT FROM OLD.last_seq THEN
+      RETURN NEW;
+    END IF;
+    SELECT
+      coalesce(array_agg(n.key ORDER BY n.key), '{}') INTO FIELDS
+    FROM
+      jsonb_each(to_jsonb(NEW)) n
+    WHERE
+      n.value IS DISTINCT FROM to_jsonb(OLD) -> n.key;
+    IF cardinality(FIELDS) = 0 THEN
+      RETURN NEW;
+    END IF;
+    CHAT_ID := NEW.id;
+    REMOVED := ARRAY (
+      SELECT
+        unnest(OLD.members)
+      EXCEPT
+      SELECT
+        unnest(NEW.members));
+  ELSIF TG_OP = 'DELETE' THEN
+    CHAT_ID := OLD.id;
+    REMOVED := OLD.members;
+    -- the chat is gone, its members are in removed
+    CHAT := json_build_object('id', OLD.id, 'ws_id', OLD.ws_id, 'name', OLD.name, 'type', OLD.type, 'members', '[]'::json, 'created_at', OLD.created_at, 'archived_at', OLD.archived_at, 'member_count', OLD.member_count);
+  ELSE
+    CHAT_ID := NEW.id;
+  END IF;
+  RAISE NOTICE 'add_to_chat: % %', TG_OP, CHAT_ID;
+  LOOP
+    PERFORM
+      pg_notify('chat_updated', json_build_object('op', TG_OP, 'chat_id', CHAT_ID, 'fields', FIELDS, 'removed', REMOVED[1:CHUNK], 'chat', CHAT)::text);
+    REMOVED := REMOVED[CHUNK + 1:];
+    FIELDS := '{}';
+    EXIT WHEN cardinality(REMOVED) = 0;
+  END LOOP;
+  RETURN NEW;
+END;
+$$
+LANGUAGE plpgsql;
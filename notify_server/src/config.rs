@@ -18,6 +18,13 @@ pub struct ServerConfig {
     pub port: u16,
     pub db_url: String,
     pub base_dir: PathBuf,
+    //presence is counted per instance, instances sharing a database need distinct ids
+    #[serde(default = "default_instance_id")]
+    pub instance_id: String,
+}
+
+fn default_instance_id() -> String {
+    "notify".to_string()
 }
 
 impl AppConfig {
@@ -2,6 +2,7 @@ use std::{ops::Deref, sync::Arc};
 mod config;
 mod error;
 mod notify;
+mod presence;
 
 use axum::{
     middleware::from_fn_with_state,
@@ -15,6 +16,7 @@ use core_lib::{verify_token, DecodingKey, TokenVerify, User};
 use dashmap::DashMap;
 use error::AppError;
 pub use notify::setup_pg_listener;
+use sqlx::PgPool;
 
 use notify::AppEvent;
 
@@ -29,6 +31,7 @@ pub struct AppStateInner {
     pub config: AppConfig,
     users: UserMap,
     dk: DecodingKey,
+    pool: PgPool,
 }
 impl TokenVerify for AppState {
     type Error = AppError;
@@ -43,16 +46,25 @@ impl Deref for AppState {
     }
 }
 impl AppState {
-    pub fn try_new(config: AppConfig) -> Result<Self, AppError> {
+    pub async fn try_new(config: AppConfig) -> Result<Self, AppError> {
         let dk = DecodingKey::load(&config.auth.pk)?;
         let users = Arc::new(DashMap::new());
-        Ok(Self(Arc::new(AppStateInner { config, dk, users })))
+        let pool = PgPool::connect(&config.server.db_url).await?;
+        Ok(Self(Arc::new(AppStateInner {
+            config,
+            dk,
+            users,
+            pool,
+        })))
     }
 }
 
 const INDEX_HTML: &str = include_str!("../index.html");
 pub async fn get_router(config: AppConfig) -> anyhow::Result<Router> {
-    let state = AppState::try_new(config).expect("app state init failed");
+    let state = AppState::try_new(config)
+        .await
+        .expect("app state init failed");
+    presence::reset(&state.pool, &state.config.server.instance_id).await?;
     setup_pg_listener(state.clone()).await?;
     let router = Router::new()
         .route("/events", get(sse_handler))
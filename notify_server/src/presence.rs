@@ -0,0 +1,84 @@
+use sqlx::PgPool;
+use tracing::warn;
+
+//users are online while holding at least one event stream on any instance
+pub(crate) async fn connect(
+    pool: &PgPool,
+    instance_id: &str,
+    user_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_presence(user_id,instance_id,connections,last_seen_at)
+        VALUES($1,$2,1,now())
+        ON CONFLICT(user_id,instance_id) DO UPDATE SET connections=user_presence.connections+1,last_seen_at=now()
+        "#,
+    )
+    .bind(user_id)
+    .bind(instance_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn disconnect(
+    pool: &PgPool,
+    instance_id: &str,
+    user_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE user_presence
+        SET connections=GREATEST(connections-1,0),last_seen_at=now()
+        WHERE user_id=$1 and instance_id=$2
+        "#,
+    )
+    .bind(user_id)
+    .bind(instance_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+//streams don't survive a restart of notify_server, the streams held by other
+//instances are left alone
+pub(crate) async fn reset(pool: &PgPool, instance_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE user_presence SET connections=0 WHERE instance_id=$1 and connections > 0")
+        .bind(instance_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+//moved into the event stream, marks the user offline when the stream is dropped
+pub(crate) struct PresenceGuard {
+    pool: PgPool,
+    instance_id: String,
+    user_id: i64,
+}
+
+impl PresenceGuard {
+    pub(crate) async fn new(pool: PgPool, instance_id: String, user_id: i64) -> Self {
+        if let Err(e) = connect(&pool, &instance_id, user_id).await {
+            warn!("failed to record presence of user {}: {:?}", user_id, e);
+        }
+        Self {
+            pool,
+            instance_id,
+            user_id,
+        }
+    }
+}
+
+impl Drop for PresenceGuard {
+    fn drop(&mut self) {
+        let pool = self.pool.clone();
+        let instance_id = std::mem::take(&mut self.instance_id);
+        let user_id = self.user_id;
+        tokio::spawn(async move {
+            if let Err(e) = disconnect(&pool, &instance_id, user_id).await {
+                warn!("failed to clear presence of user {}: {:?}", user_id, e);
+            }
+        });
+    }
+}
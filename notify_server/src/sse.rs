@@ -13,7 +13,7 @@ use tokio::sync::broadcast;
 use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 use tracing::info;
 
-use crate::{notify::AppEvent, AppState};
+use crate::{notify::AppEvent, presence::PresenceGuard, AppState};
 
 pub(crate) async fn sse_handler(
     Extension(user): Extension<User>,
@@ -36,6 +36,12 @@ pub(crate) async fn sse_handler(
     };
 
     // info!("users {}", state.users.len());
+    let presence = PresenceGuard::new(
+        state.pool.clone(),
+        state.config.server.instance_id.clone(),
+        user.id,
+    )
+    .await;
 
     let stream = BroadcastStream::new(rx)
         .filter_map(
@@ -44,7 +50,9 @@ pub(crate) async fn sse_handler(
                 tokio_stream::wrappers::errors::BroadcastStreamRecvError,
             >| v.ok(),
         )
-        .map(|v| {
+        .map(move |v| {
+            //the stream owns the guard, the user goes offline when it is dropped
+            let _ = &presence;
             // info!("sending event: {:?}", v);
             let name = match v.as_ref() {
                 AppEvent::NewChat(_) => "NewChat",
@@ -52,6 +60,23 @@ pub(crate) async fn sse_handler(
                 AppEvent::AddToChat(_) => "AddToChat",
                 AppEvent::RemoveFromChat(_) => "RemoveFromChat",
                 AppEvent::UpdateChatName(_) => "UpdateChatName",
+                AppEvent::ArchiveChat(_) => "ArchiveChat",
+                AppEvent::UnarchiveChat(_) => "UnarchiveChat",
+                AppEvent::UpdateChatMetadata(_) => "UpdateChatMetadata",
+                AppEvent::ChatPolicyUpdated(_) => "ChatPolicyUpdated",
+                AppEvent::SilentMessage(_) => "SilentMessage",
+                AppEvent::ReadMarkerUpdated(_) => "ReadMarkerUpdated",
+                AppEvent::SidebarSectionUpdated(_) => "SidebarSectionUpdated",
+                AppEvent::SidebarSectionDeleted(_) => "SidebarSectionDeleted",
+                AppEvent::ChatSidebarUpdated(_) => "ChatSidebarUpdated",
+                AppEvent::MessageEdited(_) => "MessageEdited",
+                AppEvent::MessageDeleted(_) => "MessageDeleted",
+                AppEvent::NewReply(_) => "NewReply",
+                AppEvent::ReactionAdded(_) => "ReactionAdded",
+                AppEvent::ReactionRemoved(_) => "ReactionRemoved",
+                AppEvent::MessagePinned(_) => "MessagePinned",
+                AppEvent::MessageUnpinned(_) => "MessageUnpinned",
+                AppEvent::Mention(_) => "Mention",
             };
             Ok(Event::default()
                 .data(serde_json::to_string(&v).expect("Failed to serialize event"))